@@ -0,0 +1,78 @@
+//! Measures how long it takes to insert a large amount of words and to complete on them.
+//!
+//! Compare the default `BTreeMap` backed nodes with the `HashMap` backed nodes using:
+//!
+//! ```text
+//! cargo run --release --example insert_bench
+//! cargo run --release --example insert_bench --features fast-insert
+//! ```
+//!
+//! With the `rayon` feature enabled parallel insertion is measured as well:
+//!
+//! ```text
+//! cargo run --release --example insert_bench --features rayon
+//! ```
+
+extern crate rs_complete;
+
+use rs_complete::CompletionTree;
+use std::time::Instant;
+
+const WORDS: usize = 200_000;
+
+/// Generates pseudo random lowercase words using a fixed seed so runs are comparable
+fn words() -> Vec<String> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    (0..WORDS)
+        .map(|_| {
+            let len = 5 + (next() % 10) as usize;
+            (0..len)
+                .map(|_| (b'a' + (next() % 26) as u8) as char)
+                .collect()
+        })
+        .collect()
+}
+
+fn main() {
+    let words = words();
+    let mut tree = CompletionTree::default();
+
+    let start = Instant::now();
+    words.iter().for_each(|word| tree.insert(word));
+    let insert = start.elapsed();
+
+    let start = Instant::now();
+    let completions = ["a", "bat", "qu", "zz", "mo"]
+        .iter()
+        .map(|prefix| tree.complete(prefix).map_or(0, |c| c.len()))
+        .sum::<usize>();
+    let complete = start.elapsed();
+
+    println!(
+        "inserted {} words ({} nodes) in {:?}",
+        tree.word_count(),
+        tree.size(),
+        insert
+    );
+    println!("collected {} completions in {:?}", completions, complete);
+
+    #[cfg(feature = "rayon")]
+    {
+        drop(tree);
+        let lines = words.iter().map(String::as_str).collect::<Vec<&str>>();
+        let mut parallel = CompletionTree::default();
+        let start = Instant::now();
+        parallel.par_insert_all(&lines);
+        println!(
+            "inserted {} words in parallel in {:?}",
+            parallel.word_count(),
+            start.elapsed()
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use alloc::collections::BTreeMap;
+#[cfg(not(feature = "std"))]
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::str::Chars;
+
+/// A completion tree that associates a value with every stored word
+///
+/// Unlike [crate::CompletionTree] words are stored exactly as inserted, no characters are
+/// filtered and no splitting is performed on insert.
+///
+/// # Example
+/// ```
+/// extern crate rs_complete;
+/// use rs_complete::CompletionMap;
+///
+/// let mut commands = CompletionMap::default();
+/// commands.insert("connect", 1);
+/// commands.insert("config", 2);
+/// commands.insert("quit", 3);
+/// assert_eq!(
+///     commands.complete("con"),
+///     Some(vec![("config".to_string(), &2), ("connect".to_string(), &1)]));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CompletionMap<V> {
+    root: MapNode<V>,
+}
+
+impl<V> Default for CompletionMap<V> {
+    fn default() -> Self {
+        Self {
+            root: MapNode::new(),
+        }
+    }
+}
+
+impl<V> CompletionMap<V> {
+    /// Inserts a word with an associated value. If the word already exists its value is
+    /// replaced and the previous value is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `word`    The word to insert
+    /// * `value`   The value to associate with the word
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionMap;
+    ///
+    /// let mut commands = CompletionMap::default();
+    /// assert_eq!(commands.insert("quit", 1), None);
+    /// assert_eq!(commands.insert("quit", 2), Some(1));
+    /// assert_eq!(commands.get("quit"), Some(&2));
+    /// ```
+    pub fn insert(&mut self, word: &str, value: V) -> Option<V> {
+        self.root.insert(word.chars(), value)
+    }
+
+    /// Returns the value associated with the exact word
+    ///
+    /// # Arguments
+    ///
+    /// * `word`    The word to look up
+    pub fn get(&self, word: &str) -> Option<&V> {
+        self.root
+            .find(word.chars())
+            .and_then(|node| node.value.as_ref())
+    }
+
+    /// Returns a mutable reference to the value associated with the exact word
+    ///
+    /// # Arguments
+    ///
+    /// * `word`    The word to look up
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionMap;
+    ///
+    /// let mut commands = CompletionMap::default();
+    /// commands.insert("quit", 1);
+    /// *commands.get_mut("quit").unwrap() += 1;
+    /// assert_eq!(commands.get("quit"), Some(&2));
+    /// ```
+    pub fn get_mut(&mut self, word: &str) -> Option<&mut V> {
+        self.root
+            .find_mut(word.chars())
+            .and_then(|node| node.value.as_mut())
+    }
+
+    /// Returns the values of all words starting with `prefix`, ordered by word
+    pub(crate) fn values_with_prefix(&self, prefix: &str) -> Vec<&V> {
+        let mut values = vec![];
+        if let Some(node) = self.root.find(prefix.chars()) {
+            node.collect_values(&mut values);
+        }
+        values
+    }
+
+    /// Removes a word from the map and returns its value if it existed
+    ///
+    /// # Arguments
+    ///
+    /// * `word`    The word to remove
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionMap;
+    ///
+    /// let mut commands = CompletionMap::default();
+    /// commands.insert("quit", 1);
+    /// assert_eq!(commands.remove("quit"), Some(1));
+    /// assert_eq!(commands.remove("quit"), None);
+    /// ```
+    pub fn remove(&mut self, word: &str) -> Option<V> {
+        self.root.remove(word.chars())
+    }
+
+    /// Returns an optional vector of completions together with their values based on the
+    /// provided input. Completions are sorted alphabetically.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed. A line ending in
+    ///   whitespace has no word to complete.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionMap;
+    ///
+    /// let mut commands = CompletionMap::default();
+    /// commands.insert("connect", 1);
+    /// assert_eq!(
+    ///     commands.complete("help con"),
+    ///     Some(vec![("help connect".to_string(), &1)]));
+    /// assert_eq!(commands.complete("con "), None);
+    /// ```
+    pub fn complete(&self, line: &str) -> Option<Vec<(String, &V)>> {
+        if line.is_empty() || line.ends_with(char::is_whitespace) {
+            return None;
+        }
+        let last_word = line.split_whitespace().last().unwrap_or("");
+        let node = self.root.find(last_word.chars())?;
+        let mut completions = vec![];
+        node.collect("".to_string(), &mut completions);
+        Some(
+            completions
+                .into_iter()
+                .map(|(ext, value)| (format!("{}{}", line, ext), value))
+                .collect(),
+        )
+    }
+
+    /// Clears all the data from the map
+    pub fn clear(&mut self) {
+        self.root = MapNode::new();
+    }
+
+    /// Returns a count of how many words that exist in the map
+    pub fn word_count(&self) -> u32 {
+        self.root.word_count()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MapNode<V> {
+    subnodes: BTreeMap<char, MapNode<V>>,
+    value: Option<V>,
+}
+
+impl<V> MapNode<V> {
+    fn new() -> Self {
+        Self {
+            subnodes: BTreeMap::new(),
+            value: None,
+        }
+    }
+
+    fn word_count(&self) -> u32 {
+        let count = self.subnodes.values().map(|n| n.word_count()).sum();
+        if self.value.is_some() {
+            count + 1
+        } else {
+            count
+        }
+    }
+
+    fn insert(&mut self, mut iter: Chars, value: V) -> Option<V> {
+        if let Some(c) = iter.next() {
+            self.subnodes
+                .entry(c)
+                .or_insert_with(MapNode::new)
+                .insert(iter, value)
+        } else {
+            self.value.replace(value)
+        }
+    }
+
+    fn remove(&mut self, mut iter: Chars) -> Option<V> {
+        if let Some(c) = iter.next() {
+            let subnode = self.subnodes.get_mut(&c)?;
+            let removed = subnode.remove(iter);
+            if subnode.value.is_none() && subnode.subnodes.is_empty() {
+                self.subnodes.remove(&c);
+            }
+            removed
+        } else {
+            self.value.take()
+        }
+    }
+
+    fn find(&self, mut iter: Chars) -> Option<&MapNode<V>> {
+        if let Some(c) = iter.next() {
+            self.subnodes.get(&c)?.find(iter)
+        } else {
+            Some(self)
+        }
+    }
+
+    fn find_mut(&mut self, mut iter: Chars) -> Option<&mut MapNode<V>> {
+        if let Some(c) = iter.next() {
+            self.subnodes.get_mut(&c)?.find_mut(iter)
+        } else {
+            Some(self)
+        }
+    }
+
+    fn collect_values<'a>(&'a self, values: &mut Vec<&'a V>) {
+        if let Some(value) = &self.value {
+            values.push(value);
+        }
+        for node in self.subnodes.values() {
+            node.collect_values(values);
+        }
+    }
+
+    fn collect<'a>(&'a self, partial: String, completions: &mut Vec<(String, &'a V)>) {
+        if let Some(value) = &self.value {
+            completions.push((partial.clone(), value));
+        }
+        for (c, node) in &self.subnodes {
+            let mut partial = partial.clone();
+            partial.push(*c);
+            node.collect(partial, completions);
+        }
+    }
+}
@@ -1,32 +1,559 @@
-use std::collections::{BTreeMap, BTreeSet};
-use std::str::Chars;
-use std::sync::Arc;
+use crate::{CompleteError, CompletionMap, InclusionError};
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeSet, BinaryHeap};
+use alloc::sync::Arc;
+#[cfg(not(feature = "std"))]
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::cmp::{Ordering, Reverse};
+use core::fmt;
+use core::iter::FromIterator;
+use core::mem::size_of;
+use core::ops::{Bound, Range};
+use core::str::Chars;
+use core::sync::atomic::{self, AtomicUsize};
+#[cfg(feature = "fast-insert")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::io::{self, BufRead};
+
+mod builder;
+mod bytes;
+#[cfg(feature = "rustyline")]
+mod completer;
+
+pub use builder::CompletionTreeBuilder;
+pub use bytes::ParseError;
+#[cfg(feature = "rustyline")]
+pub use completer::TreeCompleter;
+
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "regex")]
+use regex::Regex;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "unicode-normalization")]
+use unicode_normalization::UnicodeNormalization;
+#[cfg(feature = "unicode-segmentation")]
+use unicode_segmentation::UnicodeSegmentation;
 
 /// Word separation type used by CompletionTree
-#[derive(Debug, Clone, PartialEq)]
+///
+/// With the `regex` feature enabled [WordSeparator::Regex] splits on matches of a regular
+/// expression. Regex separators are compared, and serialized, using their pattern.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub enum WordSeparator {
+    /// Splits on any amount of whitespace
     Whitespace,
+    /// Splits on every occurrence of the string
     Separator(&'static str),
+    /// Splits on any of the characters. Empty words between adjacent separators are skipped.
+    AnyOf(Vec<char>),
+    /// Splits on every occurrence of the string, like [WordSeparator::Separator]
+    Owned(String),
+    /// Splits on every match of the regular expression. Empty words are skipped.
+    #[cfg(feature = "regex")]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_regex"))]
+    Regex(Regex),
+}
+
+impl PartialEq for WordSeparator {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (WordSeparator::Whitespace, WordSeparator::Whitespace) => true,
+            (WordSeparator::Separator(a), WordSeparator::Separator(b)) => a == b,
+            (WordSeparator::AnyOf(a), WordSeparator::AnyOf(b)) => a == b,
+            (WordSeparator::Owned(a), WordSeparator::Owned(b)) => a == b,
+            #[cfg(feature = "regex")]
+            (WordSeparator::Regex(a), WordSeparator::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(all(feature = "serde", feature = "regex"))]
+fn serialize_regex<S: Serializer>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(regex.as_str())
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(rename = "WordSeparator")]
+enum OwnedWordSeparator {
+    Whitespace,
+    Separator(String),
+    AnyOf(Vec<char>),
+    Owned(String),
+    #[cfg(feature = "regex")]
+    Regex(String),
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for WordSeparator {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match OwnedWordSeparator::deserialize(deserializer)? {
+            OwnedWordSeparator::Whitespace => WordSeparator::Whitespace,
+            OwnedWordSeparator::Separator(sep) => WordSeparator::Owned(sep),
+            OwnedWordSeparator::AnyOf(chars) => WordSeparator::AnyOf(chars),
+            OwnedWordSeparator::Owned(sep) => WordSeparator::Owned(sep),
+            #[cfg(feature = "regex")]
+            OwnedWordSeparator::Regex(pattern) => {
+                WordSeparator::Regex(Regex::new(&pattern).map_err(serde::de::Error::custom)?)
+            }
+        })
+    }
+}
+
+type SplitFn = dyn Fn(&str) -> Vec<&str> + Send + Sync;
+
+/// A custom word splitter set using [CompletionTree::set_splitter]
+#[derive(Clone)]
+struct Splitter(Arc<SplitFn>);
+
+impl fmt::Debug for Splitter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Splitter")
+    }
 }
 
 /// A completion tree that holds and handles completions
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(remote = "Self")
+)]
 pub struct CompletionTree {
     root: CompletionNode,
-    inclusions: Arc<BTreeSet<char>>,
+    inclusions: BTreeSet<char>,
+    exclusions: BTreeSet<char>,
     min_word_len: usize,
+    max_word_len: Option<usize>,
     separator: WordSeparator,
+    case_insensitive: bool,
+    normalize: bool,
+    fold_diacritics: bool,
+    min_completion_len: usize,
+    suffixes: Option<CompletionNode>,
+    insert_seq: u64,
+    insert_order: Option<BTreeMap<String, u64>>,
+    strip_ansi: bool,
+    ignore_numeric: bool,
+    subword_indexing: bool,
+    complete_empty: bool,
+    max_results: Option<usize>,
+    graphemes: bool,
+    truncate_on_invalid: bool,
+    strict_charset: bool,
+    capacity: Option<usize>,
+    sort_order: SortOrder,
+    aliases: Aliases,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    recency: Option<Recency>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    word_count: u32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    splitter: Option<Splitter>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "serialize_enabled",
+            deserialize_with = "deserialize_enabled"
+        )
+    )]
+    substrings: Option<SubstringIndex>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "serialize_enabled",
+            deserialize_with = "deserialize_enabled"
+        )
+    )]
+    infixes: Option<SubstringIndex>,
+}
+
+/// Statistics about the words of a line inserted using [CompletionTree::insert_counting]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InsertStats {
+    /// The amount of words inserted
+    pub inserted: usize,
+    /// The amount of words skipped for being shorter than [CompletionTree::min_word_len]
+    pub skipped_short: usize,
+    /// The amount of words skipped for being longer than [CompletionTree::max_word_len]
+    pub skipped_long: usize,
+    /// The amount of words skipped by other rules, like [CompletionTree::set_ignore_numeric]
+    pub skipped_other: usize,
+}
+
+/// The order [CompletionTree::complete] returns completions in, see
+/// [CompletionTree::set_sort_order]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SortOrder {
+    /// Alphabetical order, the default
+    #[default]
+    Alphabetical,
+    /// Reverse alphabetical order
+    AlphabeticalReverse,
+    /// Shortest completions first, completions of the same length in alphabetical order
+    ShortestFirst,
+    /// Longest completions first, completions of the same length in alphabetical order
+    LongestFirst,
+}
+
+impl SortOrder {
+    fn compare(self, a: &str, b: &str) -> Ordering {
+        let length = || a.chars().count().cmp(&b.chars().count());
+        match self {
+            SortOrder::Alphabetical => a.cmp(b),
+            SortOrder::AlphabeticalReverse => b.cmp(a),
+            SortOrder::ShortestFirst => length().then_with(|| a.cmp(b)),
+            SortOrder::LongestFirst => length().reverse().then_with(|| a.cmp(b)),
+        }
+    }
+}
+
+/// Completions returned by [CompletionTree::complete_detailed]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompleteResult {
+    /// True if the completed word is itself a word in the tree
+    pub exact: bool,
+    /// The completions, like [CompletionTree::complete] returns them
+    pub completions: Vec<String>,
+}
+
+/// Why a word wasn't inserted
+enum Skip {
+    Short,
+    Long,
+    Other,
+}
+
+/// Tracks when the words of a tree with a capacity were last used. The times are only updated
+/// through shared references when completing, so they are stored atomically.
+///
+/// Words are queued by time of use, but a use through a shared reference can't move the word
+/// in the queue. Each word stays queued at the time it was queued until it reaches the front,
+/// where it's queued again at its latest use if it was used since.
+#[derive(Debug, Default)]
+struct Recency {
+    clock: AtomicUsize,
+    used: BTreeMap<Arc<str>, Stamp>,
+    queue: BTreeMap<usize, Arc<str>>,
+}
+
+/// The times a tracked word was queued at and last used
+#[derive(Debug)]
+struct Stamp {
+    queued: usize,
+    used: AtomicUsize,
+}
+
+impl Clone for Recency {
+    fn clone(&self) -> Self {
+        Self {
+            clock: AtomicUsize::new(self.clock.load(atomic::Ordering::Relaxed)),
+            used: self
+                .used
+                .iter()
+                .map(|(word, stamp)| {
+                    let stamp = Stamp {
+                        queued: stamp.queued,
+                        used: AtomicUsize::new(stamp.used.load(atomic::Ordering::Relaxed)),
+                    };
+                    (word.clone(), stamp)
+                })
+                .collect(),
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl Recency {
+    fn tick(&self) -> usize {
+        self.clock.fetch_add(1, atomic::Ordering::Relaxed)
+    }
+
+    /// Marks the stored word as used, adding it if it isn't tracked yet
+    fn touch(&mut self, stored: &str) {
+        let now = self.tick();
+        match self.used.get(stored) {
+            Some(stamp) => stamp.used.store(now, atomic::Ordering::Relaxed),
+            None => {
+                let word = Arc::<str>::from(stored);
+                let stamp = Stamp {
+                    queued: now,
+                    used: AtomicUsize::new(now),
+                };
+                self.queue.insert(now, word.clone());
+                self.used.insert(word, stamp);
+            }
+        }
+    }
+
+    /// Marks the stored word as used if it's tracked
+    fn mark_used(&self, stored: &str) {
+        if let Some(stamp) = self.used.get(stored) {
+            stamp.used.store(self.tick(), atomic::Ordering::Relaxed);
+        }
+    }
+
+    /// Stops tracking the stored word
+    fn remove(&mut self, stored: &str) {
+        if let Some(stamp) = self.used.remove(stored) {
+            self.queue.remove(&stamp.queued);
+        }
+    }
+
+    /// Stops tracking the words for which `keep` returns false
+    fn retain<F: FnMut(&str) -> bool>(&mut self, mut keep: F) {
+        self.used.retain(|word, _| keep(word));
+        let used = &self.used;
+        self.queue.retain(|_, word| used.contains_key(word));
+    }
+
+    fn clear(&mut self) {
+        self.used.clear();
+        self.queue.clear();
+    }
+
+    /// Returns the least recently used word, requeueing the words used since they were queued
+    /// on the way. Every word is requeued at most once per use, so this is amortized
+    /// logarithmic.
+    fn least_recent(&mut self) -> Option<Arc<str>> {
+        loop {
+            let (queued, word) = self.queue.iter().next().map(|(t, w)| (*t, w.clone()))?;
+            let stamp = match self.used.get_mut(&word) {
+                Some(stamp) => stamp,
+                None => {
+                    self.queue.remove(&queued);
+                    continue;
+                }
+            };
+            let used = *stamp.used.get_mut();
+            if used == queued {
+                return Some(word);
+            }
+            stamp.queued = used;
+            self.queue.remove(&queued);
+            self.queue.insert(used, word);
+        }
+    }
+}
+
+/// Maps folded aliases to the words they expand to
+type Aliases = BTreeMap<String, BTreeSet<String>>;
+
+/// Maps keys taken from the stored words, like their suffixes, to the words they were taken from.
+/// Every key of a word shares the same allocation of the word.
+type SubstringIndex = CompletionMap<BTreeSet<Arc<str>>>;
+
+#[cfg(feature = "serde")]
+fn serialize_enabled<S: Serializer>(
+    index: &Option<SubstringIndex>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bool(index.is_some())
+}
+
+/// Deserializes the enabled state of the substring index, the index is rebuilt from the words
+/// of the tree once it has been deserialized
+#[cfg(feature = "serde")]
+fn deserialize_enabled<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<SubstringIndex>, D::Error> {
+    Ok(bool::deserialize(deserializer)?.then(SubstringIndex::default))
 }
 
 impl Default for CompletionTree {
     fn default() -> Self {
-        let inclusions = Arc::new(BTreeSet::new());
         Self {
-            root: CompletionNode::new(inclusions.clone()),
-            inclusions,
+            root: CompletionNode::new(),
+            inclusions: BTreeSet::new(),
+            exclusions: BTreeSet::new(),
             min_word_len: 5,
+            max_word_len: None,
             separator: WordSeparator::Whitespace,
+            case_insensitive: false,
+            normalize: false,
+            fold_diacritics: false,
+            min_completion_len: 1,
+            suffixes: None,
+            insert_seq: 0,
+            insert_order: None,
+            strip_ansi: false,
+            ignore_numeric: false,
+            subword_indexing: false,
+            complete_empty: false,
+            max_results: None,
+            graphemes: false,
+            truncate_on_invalid: true,
+            strict_charset: false,
+            capacity: None,
+            sort_order: SortOrder::Alphabetical,
+            aliases: Aliases::new(),
+            recency: None,
+            word_count: 0,
+            splitter: None,
+            substrings: None,
+            infixes: None,
+        }
+    }
+}
+
+/// Collects words into a default configured CompletionTree. Each item is passed through
+/// [CompletionTree::insert] so items containing multiple words are split.
+///
+/// # Example
+/// ```
+/// extern crate rs_complete;
+/// use rs_complete::CompletionTree;
+///
+/// let completions: CompletionTree = vec!["batman robin", "batmobile"].into_iter().collect();
+/// assert_eq!(completions.word_count(), 3);
+/// ```
+impl<'a> FromIterator<&'a str> for CompletionTree {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> Self {
+        let mut tree = CompletionTree::default();
+        iter.into_iter().for_each(|line| tree.insert(line));
+        tree
+    }
+}
+
+/// Collects words into a default configured CompletionTree. Each item is passed through
+/// [CompletionTree::insert] so items containing multiple words are split.
+impl FromIterator<String> for CompletionTree {
+    fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> Self {
+        let mut tree = CompletionTree::default();
+        iter.into_iter().for_each(|line| tree.insert(&line));
+        tree
+    }
+}
+
+/// Inserts every item using [CompletionTree::insert], honoring the current configuration of
+/// the tree.
+///
+/// # Example
+/// ```
+/// extern crate rs_complete;
+/// use rs_complete::{CompletionTree, WordSeparator};
+///
+/// let mut completions = CompletionTree::default();
+/// completions.separator(WordSeparator::Separator("|"));
+/// completions.extend(vec!["batman|robin", "batmobile"]);
+/// assert_eq!(completions.word_count(), 3);
+/// ```
+impl<'a> Extend<&'a str> for CompletionTree {
+    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|line| self.insert(line));
+    }
+}
+
+/// Inserts every item using [CompletionTree::insert], honoring the current configuration of
+/// the tree.
+impl Extend<String> for CompletionTree {
+    fn extend<I: IntoIterator<Item = String>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|line| self.insert(&line));
+    }
+}
+
+/// Writes the words of the tree one per line. Words are written in the order they are stored
+/// in the tree, which is alphabetical unless [CompletionTree::set_case_insensitive] is used.
+/// Use `{:?}` to dump the node structure instead.
+///
+/// # Example
+/// ```
+/// extern crate rs_complete;
+/// use rs_complete::CompletionTree;
+///
+/// let mut completions = CompletionTree::default();
+/// completions.insert("robin batman");
+/// assert_eq!(completions.to_string(), "batman\nrobin");
+/// ```
+impl fmt::Display for CompletionTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (word, leaf)) in Leaves::new(Some(&self.root)).enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            f.write_str(leaf.display().unwrap_or(&word))?;
         }
+        Ok(())
+    }
+}
+
+/// Trees are equal when they hold the same words, as returned by [CompletionTree::words].
+/// How the words were inserted, how many times, and the configuration of the trees, like
+/// inclusions or the [WordSeparator], are not compared.
+///
+/// # Example
+/// ```
+/// extern crate rs_complete;
+/// use rs_complete::CompletionTree;
+///
+/// let mut split = CompletionTree::default();
+/// split.insert("batman robin batman");
+/// let mut separate = CompletionTree::with_inclusions(&['-']);
+/// separate.insert("robin");
+/// separate.insert("batman");
+/// assert_eq!(split, separate);
+/// separate.insert("joker");
+/// assert_ne!(split, separate);
+/// ```
+impl PartialEq for CompletionTree {
+    fn eq(&self, other: &Self) -> bool {
+        self.word_count() == other.word_count() && self.words() == other.words()
+    }
+}
+
+impl Eq for CompletionTree {}
+
+/// Iterates the words of the tree without consuming it. Words are yielded in the order they
+/// are stored in the tree, which is alphabetical unless [CompletionTree::set_case_insensitive]
+/// is used.
+///
+/// # Example
+/// ```
+/// extern crate rs_complete;
+/// use rs_complete::CompletionTree;
+///
+/// let mut completions = CompletionTree::default();
+/// completions.insert("robin batman");
+/// let mut words = vec![];
+/// for word in &completions {
+///     words.push(word);
+/// }
+/// assert_eq!(words, vec!["batman".to_string(), "robin".to_string()]);
+/// ```
+impl<'a> IntoIterator for &'a CompletionTree {
+    type Item = String;
+    type IntoIter = Words<'a>;
+
+    fn into_iter(self) -> Words<'a> {
+        Words(Leaves::new(Some(&self.root)))
+    }
+}
+
+/// An iterator over the words of a [CompletionTree], created by iterating a `&CompletionTree`
+#[derive(Debug)]
+pub struct Words<'a>(Leaves<'a>);
+
+impl<'a> Iterator for Words<'a> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.0
+            .next()
+            .map(|(word, leaf)| leaf.display().map(String::from).unwrap_or(word))
     }
 }
 
@@ -61,75 +588,213 @@ impl CompletionTree {
         incl.iter().for_each(|c| {
             set.insert(*c);
         });
-        let inclusions = Arc::new(set);
         Self {
-            root: CompletionNode::new(inclusions.clone()),
-            inclusions,
+            inclusions: set,
             ..Self::default()
         }
     }
 
-    /// Inserts one or more words into the completion tree for later use.
-    /// Input is automatically split using the defined [WordSeparator] (see [CompletionTree::separator]).
+    /// Create a new CompletionTree with provided non alphabet characters allowed in words,
+    /// like [CompletionTree::with_inclusions], but with both the upper and lower case forms of
+    /// every character allowed. Inclusions are otherwise matched exactly, so including 'Ä'
+    /// doesn't allow 'ä'.
+    ///
+    /// Letters are accepted without being included, so this only changes which characters are
+    /// accepted when they're also excluded, since inclusions take precedence over exclusions.
     ///
     /// # Arguments
     ///
-    /// * `line`    A str slice containing one or more words
+    /// * `incl`    An array slice with allowed characters
     ///
     /// # Example
     /// ```
     /// extern crate rs_complete;
     /// use rs_complete::CompletionTree;
     ///
-    /// let mut completions = CompletionTree::default();
-    /// completions.set_min_word_len(1);
+    /// let mut completions = CompletionTree::with_inclusions_ci(&['\u{c4}', '-']);
+    /// completions.set_exclusions(&['\u{c4}', '\u{e4}']);
+    /// completions.insert("\u{c4}pfel-baum \u{e4}pfel-kuchen");
+    /// assert_eq!(
+    ///     completions.complete("\u{e4}p"),
+    ///     Some(vec!["\u{e4}pfel-kuchen".to_string()]));
+    /// assert_eq!(
+    ///     completions.complete("\u{c4}p"),
+    ///     Some(vec!["\u{c4}pfel-baum".to_string()]));
+    /// ```
+    pub fn with_inclusions_ci(incl: &[char]) -> Self {
+        let incl = incl
+            .iter()
+            .flat_map(|c| c.to_lowercase().chain(c.to_uppercase()))
+            .collect::<Vec<char>>();
+        Self::with_inclusions(&incl)
+    }
+
+    /// Create a new CompletionTree with provided non alphabet characters allowed in words,
+    /// like [CompletionTree::with_inclusions], rejecting inclusions that would have no effect.
+    /// Alphanumeric characters are accepted without being included and whitespace ends words
+    /// under the default [WordSeparator], so both are most likely configuration mistakes.
+    /// Returns an error for the first such character.
     ///
-    /// // Insert multiple words
-    /// completions.insert("a line with many words");
-    /// assert_eq!(completions.word_count(), 5);
-    /// completions.clear();
-    /// assert_eq!(completions.word_count(), 0);
+    /// # Arguments
     ///
-    /// // The above line is equal to the following:
-    /// completions.insert("a");
-    /// completions.insert("line");
-    /// completions.insert("with");
-    /// completions.insert("many");
-    /// completions.insert("words");
-    /// assert_eq!(completions.word_count(), 5);
+    /// * `incl`    An array slice with allowed characters
+    ///
+    /// # Example
     /// ```
-    pub fn insert(&mut self, line: &str) {
-        match self.separator {
-            WordSeparator::Whitespace => line.split_whitespace().for_each(|w| self.insert_word(w)),
-            WordSeparator::Separator(sep) => line.split(sep).for_each(|w| self.insert_word(w)),
-        };
+    /// extern crate rs_complete;
+    /// use rs_complete::{CompletionTree, InclusionError};
+    ///
+    /// assert!(CompletionTree::try_with_inclusions(&['-', '_']).is_ok());
+    /// assert_eq!(
+    ///     CompletionTree::try_with_inclusions(&['-', 'a']).err(),
+    ///     Some(InclusionError::Alphanumeric('a')));
+    /// ```
+    pub fn try_with_inclusions(incl: &[char]) -> Result<Self, InclusionError> {
+        for c in incl {
+            if c.is_alphanumeric() {
+                return Err(InclusionError::Alphanumeric(*c));
+            }
+            if c.is_whitespace() {
+                return Err(InclusionError::Whitespace(*c));
+            }
+        }
+        Ok(Self::with_inclusions(incl))
     }
 
-    fn insert_word(&mut self, word: &str) {
-        if word.len() >= self.min_word_len {
-            self.root.insert(word.chars());
+    /// Create a new CompletionTree with provided alphanumeric characters treated as word
+    /// terminators. A word is cut off at the first excluded character, like it is for any
+    /// other character that isn't accepted.
+    ///
+    /// Inclusions take precedence over exclusions. A character that is both included and
+    /// excluded is accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `excl`    An array slice with excluded characters
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::with_exclusions(&['0', '8']);
+    /// completions.insert("port8080");
+    /// assert_eq!(
+    ///     completions.complete("po"),
+    ///     Some(vec!["port".to_string()]));
+    /// ```
+    pub fn with_exclusions(excl: &[char]) -> Self {
+        Self {
+            exclusions: excl.iter().copied().collect(),
+            ..Self::default()
         }
     }
 
-    /// Changes the word separator used by CompletionTree::insert()
-    /// If left unchanged the default is [WordSeparator::Whitespace]
+    /// Create a new CompletionTree prepared for about `capacity` words
+    ///
+    /// With the `fast-insert` feature the root map is reserved up front. The root holds one
+    /// entry per distinct first character so the reservation is capped at 128 entries, enough
+    /// for every ASCII character. Without `fast-insert` the tree is backed by `BTreeMap` which
+    /// can't preallocate and this is the same as [CompletionTree::default].
     ///
     /// # Arguments
     ///
-    /// * `separator`   A WordSeparator
+    /// * `capacity`    The expected number of words
     ///
     /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::with_capacity(1_000_000);
+    /// completions.insert("batman robin batmobile");
+    /// assert_eq!(completions.word_count(), 3);
+    /// ```
+    pub fn with_capacity(capacity: usize) -> Self {
+        #[allow(unused_mut)]
+        let mut tree = Self::default();
+        #[cfg(feature = "fast-insert")]
+        tree.root.subnodes.reserve(capacity.min(128));
+        #[cfg(not(feature = "fast-insert"))]
+        let _ = capacity;
+        tree
+    }
+
+    /// Adds a non alphabet character to the allowed characters of the tree.
+    /// See [CompletionTree::with_inclusions].
+    ///
+    /// Only future calls to `insert()` are affected. Characters that were dropped from words
+    /// inserted earlier won't retroactively appear in those words.
     ///
+    /// # Arguments
+    ///
+    /// * `c`   The character to allow
+    ///
+    /// # Example
     /// ```
     /// extern crate rs_complete;
-    /// use rs_complete::{CompletionTree, WordSeparator};
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.add_inclusion('@');
+    /// completions.insert("@batman");
+    /// assert_eq!(
+    ///     completions.complete("@bat"),
+    ///     Some(vec!["@batman".to_string()]));
+    /// ```
+    pub fn add_inclusion(&mut self, c: char) {
+        self.inclusions.insert(c);
+    }
+
+    /// Replaces the allowed non alphabet characters of the tree.
+    /// See [CompletionTree::with_inclusions].
+    ///
+    /// Only future calls to `insert()` are affected. Characters that were dropped from words
+    /// inserted earlier won't retroactively appear in those words, and words containing
+    /// characters that are no longer allowed remain in the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `incl`    An array slice with allowed characters
+    pub fn set_inclusions(&mut self, incl: &[char]) {
+        self.inclusions = incl.iter().copied().collect();
+    }
+
+    /// Replaces the excluded characters of the tree.
+    /// See [CompletionTree::with_exclusions].
+    ///
+    /// Only future calls to `insert()` are affected.
+    ///
+    /// # Arguments
+    ///
+    /// * `excl`    An array slice with excluded characters
+    pub fn set_exclusions(&mut self, excl: &[char]) {
+        self.exclusions = excl.iter().copied().collect();
+    }
+
+    /// Returns true if `c` can be part of a word
+    fn accepts(&self, c: char) -> bool {
+        self.inclusions.contains(&c)
+            || (!self.strict_charset && !self.exclusions.contains(&c) && c.is_alphanumeric())
+    }
+
+    /// Inserts one or more words into the completion tree for later use.
+    /// Input is automatically split using the defined [WordSeparator] (see [CompletionTree::separator]).
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    A string containing one or more words, like a `&str`, `String` or `Cow<str>`
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
     ///
     /// let mut completions = CompletionTree::default();
-    /// completions.separator(WordSeparator::Separator("|"));
     /// completions.set_min_word_len(1);
     ///
     /// // Insert multiple words
-    /// completions.insert("a|line|with|many|words");
+    /// completions.insert("a line with many words");
     /// assert_eq!(completions.word_count(), 5);
     /// completions.clear();
     /// assert_eq!(completions.word_count(), 0);
@@ -142,157 +807,3749 @@ impl CompletionTree {
     /// completions.insert("words");
     /// assert_eq!(completions.word_count(), 5);
     /// ```
-    pub fn separator(&mut self, separator: WordSeparator) {
-        self.separator = separator;
+    pub fn insert(&mut self, line: impl AsRef<str>) {
+        self.insert_counting(line.as_ref());
     }
 
-    /// Returns an optional vector of completions based on the provided input
+    /// Inserts a line like [CompletionTree::insert] and returns how many of its words were
+    /// inserted and how many were skipped, and why. Sub-words inserted by
+    /// [CompletionTree::set_subword_indexing] aren't included in the statistics.
     ///
     /// # Arguments
     ///
-    /// * `line`    The line to complete
-    ///             In case of multiple words, only the last will be completed
+    /// * `line`    A line of text to insert
     ///
     /// # Example
     /// ```
     /// extern crate rs_complete;
-    /// use rs_complete::CompletionTree;
+    /// use rs_complete::{CompletionTree, InsertStats};
     ///
     /// let mut completions = CompletionTree::default();
-    /// completions.insert("batman robin batmobile batcave robber");
-    /// assert_eq!(
-    ///     completions.complete("bat"),
-    ///     Some(vec!["batcave", "batman", "batmobile"].iter().map(|s| s.to_string()).collect()));
-    ///
+    /// completions.set_max_word_len(8);
     /// assert_eq!(
-    ///     completions.complete("to the bat"),
-    ///     Some(vec!["to the batcave", "to the batman", "to the batmobile"].iter().map(|s| s.to_string()).collect()));
+    ///     completions.insert_counting("to the batmobile robin"),
+    ///     InsertStats {
+    ///         inserted: 1,
+    ///         skipped_short: 2,
+    ///         skipped_long: 1,
+    ///         skipped_other: 0,
+    ///     });
     /// ```
-    pub fn complete(&self, line: &str) -> Option<Vec<String>> {
-        if !line.is_empty() {
-            let last_word = line.split_whitespace().last().unwrap_or("");
-            if let Some(mut extensions) = self.root.complete(last_word.chars()) {
-                extensions.sort();
-                return Some(
-                    extensions
-                        .iter()
-                        .map(|ext| format!("{}{}", line, ext))
-                        .collect::<Vec<String>>(),
-                );
-            } else {
-                return None;
+    pub fn insert_counting(&mut self, line: &str) -> InsertStats {
+        let line = if self.strip_ansi {
+            strip_ansi(line)
+        } else {
+            Cow::Borrowed(line)
+        };
+        let line = line.as_ref();
+        let words = self.split_line(line);
+        let mut stats = InsertStats::default();
+        for word in words {
+            match self.insert_word_counted(word, 1) {
+                Ok(()) => {
+                    stats.inserted += 1;
+                    // The parts of a skipped word are skipped with it
+                    if self.subword_indexing {
+                        subwords(word).iter().for_each(|w| self.insert_word(w));
+                    }
+                }
+                Err(Skip::Short) => stats.skipped_short += 1,
+                Err(Skip::Long) => stats.skipped_long += 1,
+                Err(Skip::Other) => stats.skipped_other += 1,
             }
         }
-        None
+        stats
     }
 
-    /// Clears all the data from the tree
+    /// Inserts lines like [CompletionTree::insert], building the tree in parallel. Words are
+    /// grouped by their first character and every group is inserted into its own branch of
+    /// the tree on the rayon thread pool. Since the branches are disjoint they are attached to
+    /// the root as they are. The result is the same as inserting the lines one by one.
+    ///
+    /// Requires the `rayon` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines`   The lines to insert, each containing one or more words
+    ///
     /// # Example
     /// ```
     /// extern crate rs_complete;
     /// use rs_complete::CompletionTree;
     ///
     /// let mut completions = CompletionTree::default();
-    /// completions.insert("batman robin batmobile batcave robber");
-    /// assert_eq!(completions.word_count(), 5);
-    /// assert_eq!(completions.size(), 24);
-    /// completions.clear();
-    /// assert_eq!(completions.size(), 1);
-    /// assert_eq!(completions.word_count(), 0);
+    /// completions.par_insert_all(&["batman robin", "batmobile"]);
+    /// assert_eq!(completions.word_count(), 3);
     /// ```
-    pub fn clear(&mut self) {
-        self.root.clear();
+    #[cfg(feature = "rayon")]
+    pub fn par_insert_all(&mut self, lines: &[&str]) {
+        type Words = Vec<(String, Option<String>)>;
+        let mut groups = BTreeMap::<char, Words>::new();
+        for line in lines {
+            let line = if self.strip_ansi {
+                strip_ansi(line)
+            } else {
+                Cow::Borrowed(*line)
+            };
+            let mut words = vec![];
+            for word in self.split_line(&line) {
+                // The parts of a skipped word are skipped with it
+                if let Ok((path, display)) = self.prepare_word(word) {
+                    words.push((path.into_owned(), display));
+                    if self.subword_indexing {
+                        for part in subwords(word) {
+                            if let Ok((path, display)) = self.prepare_word(&part) {
+                                words.push((path.into_owned(), display));
+                            }
+                        }
+                    }
+                }
+            }
+            for word in words {
+                if let Some(order) = &mut self.insert_order {
+                    if !order.contains_key(&word.0) {
+                        order.insert(word.0.clone(), self.insert_seq);
+                        self.insert_seq += 1;
+                    }
+                }
+                if let Some(c) = word.0.chars().next() {
+                    groups.entry(c).or_default().push(word);
+                }
+            }
+        }
+        let groups = groups
+            .into_iter()
+            .map(|(c, words)| (c, self.root.subnodes.remove(&c), words))
+            .collect::<Vec<_>>();
+        let branches = groups
+            .into_par_iter()
+            .map(|(c, node, words)| {
+                let mut node = node.unwrap_or_else(CompletionNode::new);
+                for (path, display) in words {
+                    node.insert(path[c.len_utf8()..].chars(), display.as_deref());
+                }
+                (c, node)
+            })
+            .collect::<Vec<_>>();
+        self.root.subnodes.extend(branches);
+        self.word_count = self.root.word_count();
+        self.sync_recency();
+        self.enforce_capacity();
+        if self.suffixes.is_some() {
+            self.build_suffix_index();
+        }
+        if self.substrings.is_some() {
+            self.build_substring_index();
+        }
+        if self.infixes.is_some() {
+            self.build_infix_index();
+        }
     }
 
-    /// Returns a count of how many words that exist in the tree
+    /// Splits `line` into words using the configured splitter or [WordSeparator]
+    fn split_line<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        if let Some(splitter) = &self.splitter {
+            (splitter.0)(line)
+        } else {
+            self.split(line)
+        }
+    }
+
+    /// Splits `line` into words using the configured [WordSeparator]
+    fn split<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        match &self.separator {
+            WordSeparator::Whitespace => line.split_whitespace().collect::<Vec<&str>>(),
+            WordSeparator::Separator(sep) => line.split(sep).collect(),
+            WordSeparator::AnyOf(chars) => {
+                line.split(&chars[..]).filter(|w| !w.is_empty()).collect()
+            }
+            WordSeparator::Owned(sep) => line.split(sep.as_str()).collect(),
+            #[cfg(feature = "regex")]
+            WordSeparator::Regex(regex) => regex.split(line).filter(|w| !w.is_empty()).collect(),
+        }
+    }
+
+    /// Inserts words from a reader, one line at a time. Each line is passed to
+    /// [CompletionTree::insert] so the whole input never has to be held in memory.
+    /// Returns the first IO error encountered while reading.
+    ///
+    /// Requires the `std` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader`  A buffered reader to read lines from
+    ///
     /// # Example
     /// ```
     /// extern crate rs_complete;
     /// use rs_complete::CompletionTree;
+    /// use std::io::Cursor;
     ///
     /// let mut completions = CompletionTree::default();
-    /// completions.insert("batman robin batmobile batcave robber");
-    /// assert_eq!(completions.word_count(), 5);
+    /// completions
+    ///     .insert_reader(Cursor::new("batman robin\nbatmobile\n"))
+    ///     .unwrap();
+    /// assert_eq!(completions.word_count(), 3);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn insert_reader<R: BufRead>(&mut self, mut reader: R) -> io::Result<()> {
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 {
+            self.insert(line.trim_end_matches(&['\n', '\r'][..]));
+            line.clear();
+        }
+        Ok(())
+    }
+
+    /// Inserts a single word, without splitting it, as if it was inserted `count` times.
+    /// Words already in the tree have `count` added to their current count. Like
+    /// [CompletionTree::insert] the word has to pass the configured rules, like the minimum
+    /// and maximum word length, to be inserted. Nothing is inserted if `count` is 0.
+    ///
+    /// This restores counts saved using [CompletionTree::iter_counts] without inserting each
+    /// word repeatedly.
+    ///
+    /// # Arguments
+    ///
+    /// * `word`    The word to insert
+    /// * `count`   The insertion count to add to the word
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert_with_count("batman", 3);
+    /// completions.insert("batmobile batman");
+    /// assert_eq!(
+    ///     completions.iter_counts().collect::<Vec<(String, u32)>>(),
+    ///     vec![("batman".to_string(), 4), ("batmobile".to_string(), 1)]);
+    /// ```
+    pub fn insert_with_count(&mut self, word: &str, count: u32) {
+        if count > 0 {
+            // Words failing the configured rules are skipped silently
+            let _ = self.insert_word_counted(word, count);
+        }
+    }
+
+    fn insert_word(&mut self, word: &str) {
+        // Words failing the configured rules are skipped silently
+        let _ = self.insert_word_counted(word, 1);
+    }
+
+    /// Inserts a single word, without splitting it, bypassing the minimum and maximum word
+    /// length and [CompletionTree::set_ignore_numeric]. This allows short but important words,
+    /// like commands, to be added to a tree filtering out short words from bulk text. The
+    /// word is still folded and cut off at, or rejected for, the first character that isn't
+    /// accepted, see [CompletionTree::set_truncate_on_invalid].
+    ///
+    /// # Arguments
+    ///
+    /// * `word`    The word to insert
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("go ls");
+    /// assert_eq!(completions.word_count(), 0);
+    /// completions.insert_exact("ls");
+    /// assert_eq!(completions.complete("l"), Some(vec!["ls".to_string()]));
+    /// ```
+    pub fn insert_exact(&mut self, word: &str) {
+        if let Some(word) = self.shaped(word) {
+            let (path, display) = self.stored_path(self.truncated(word));
+            self.insert_prepared(&path, display, 1);
+        }
+    }
+
+    fn insert_word_counted(&mut self, word: &str, count: u32) -> Result<(), Skip> {
+        let (path, display) = self.prepare_word(word)?;
+        self.insert_prepared(&path, display, count);
+        Ok(())
+    }
+
+    /// Inserts a word already prepared for the tree at `path` into the tree and its indexes
+    fn insert_prepared(&mut self, path: &str, display: Option<String>, count: u32) {
+        // An empty path would mark the root as a word
+        if path.is_empty() {
+            return;
+        }
+        let previous = if self.substrings.is_some() || self.infixes.is_some() {
+            self.stored_word(path)
+        } else {
+            None
+        };
+        if self
+            .root
+            .insert_counted(path.chars(), display.as_deref(), count)
+        {
+            self.word_count += 1;
+            if let Some(order) = &mut self.insert_order {
+                order.insert(path.to_string(), self.insert_seq);
+                self.insert_seq += 1;
+            }
+        }
+        if let Some(recency) = &mut self.recency {
+            recency.touch(path);
+        }
+        if let Some(suffixes) = &mut self.suffixes {
+            let reversed = path.chars().rev().collect::<String>();
+            suffixes.insert(reversed.chars(), display.as_deref());
+        }
+        if self.substrings.is_some() || self.infixes.is_some() {
+            let word: Arc<str> = display.unwrap_or_else(|| path.to_string()).into();
+            let previous = previous.filter(|p| **p != *word);
+            if let Some(index) = &mut self.substrings {
+                if let Some(previous) = &previous {
+                    unindex_substrings(index, path, previous);
+                }
+                index_substrings(index, path, &word);
+            }
+            if let Some(index) = &mut self.infixes {
+                if let Some(previous) = &previous {
+                    unindex_infixes(index, previous);
+                }
+                index_infixes(index, &word);
+            }
+        }
+        self.enforce_capacity();
+    }
+
+    /// Returns the word stored at the path `stored`, in the form it is completed to
+    fn stored_word(&self, stored: &str) -> Option<String> {
+        let leaf = self
+            .root
+            .find(stored.chars())
+            .filter(|node| node.is_leaf())?;
+        Some(
+            leaf.display()
+                .map(String::from)
+                .unwrap_or_else(|| stored.to_string()),
+        )
+    }
+
+    /// Applies the configured rules to `word`. Returns the path to insert into the tree
+    /// together with the display form of the word, or why the word should be skipped.
+    fn prepare_word<'a>(&self, word: &'a str) -> Result<(Cow<'a, str>, Option<String>), Skip> {
+        let word = self.shaped(word).ok_or(Skip::Other)?;
+        if word.len() < self.min_word_len {
+            return Err(Skip::Short);
+        }
+        if self.max_word_len.is_some_and(|max| word.len() > max) {
+            return Err(Skip::Long);
+        }
+        if self.ignore_numeric && word.chars().all(|c| c.is_ascii_digit()) {
+            return Err(Skip::Other);
+        }
+        let word = self.truncated(word);
+        if word.is_empty() {
+            return Err(Skip::Other);
+        }
+        Ok(self.stored_path(word))
+    }
+
+    /// Normalizes `word` and, in grapheme mode, cuts it off at whole grapheme clusters.
+    /// Returns None if nothing is left of the word, or if it contains a character that isn't
+    /// accepted while truncation is disabled.
+    fn shaped<'a>(&self, word: &'a str) -> Option<Cow<'a, str>> {
+        let word = self.normalized(word);
+        if !self.truncate_on_invalid && !word.chars().all(|c| self.accepts(c)) {
+            return None;
+        }
+        #[cfg(feature = "unicode-segmentation")]
+        if self.graphemes {
+            return self.whole_graphemes(word);
+        }
+        Some(word)
+    }
+
+    /// Cuts `word` off before the first character that isn't accepted
+    fn truncated<'a>(&self, word: Cow<'a, str>) -> Cow<'a, str> {
+        match word.char_indices().find(|(_, c)| !self.accepts(*c)) {
+            None => word,
+            Some((end, _)) => match word {
+                Cow::Borrowed(word) => Cow::Borrowed(&word[..end]),
+                Cow::Owned(mut word) => {
+                    word.truncate(end);
+                    Cow::Owned(word)
+                }
+            },
+        }
+    }
+
+    /// Returns the path to insert the truncated `word` at together with its display form, if
+    /// it's stored in a folded form. The charset was checked on `word`, so the folded path
+    /// isn't cut off again.
+    fn stored_path<'a>(&self, word: Cow<'a, str>) -> (Cow<'a, str>, Option<String>) {
+        if self.case_insensitive || self.fold_diacritics {
+            let path = self.fold(&word).into_owned();
+            (Cow::Owned(path), Some(word.into_owned()))
+        } else {
+            (word, None)
+        }
+    }
+
+    /// Cuts `word` off before the first grapheme cluster containing a character that isn't
+    /// accepted, so the tree never stores part of a cluster. Returns None if nothing is left.
+    #[cfg(feature = "unicode-segmentation")]
+    fn whole_graphemes<'a>(&self, word: Cow<'a, str>) -> Option<Cow<'a, str>> {
+        let end = word
+            .grapheme_indices(true)
+            .find(|(_, g)| !g.chars().all(|c| self.accepts(c)))
+            .map_or(word.len(), |(i, _)| i);
+        match word {
+            _ if end == 0 => None,
+            Cow::Borrowed(word) => Some(Cow::Borrowed(&word[..end])),
+            Cow::Owned(mut word) => {
+                word.truncate(end);
+                Some(Cow::Owned(word))
+            }
+        }
+    }
+
+    /// Builds an index of all words in the tree, stored in reverse, which enables
+    /// [CompletionTree::complete_suffix]. The index is kept up to date on insert and
+    /// [CompletionTree::remove] once built. This roughly doubles the memory used by the tree.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("running jumping");
+    /// assert_eq!(completions.complete_suffix("ing"), None);
+    /// completions.build_suffix_index();
+    /// completions.insert("batman");
+    /// assert_eq!(
+    ///     completions.complete_suffix("ing"),
+    ///     Some(vec!["jumping".to_string(), "running".to_string()]));
+    /// ```
+    pub fn build_suffix_index(&mut self) {
+        let mut suffixes = CompletionNode::new();
+        let mut leaves = vec![];
+        self.root.collect_leaves("".to_string(), &mut leaves);
+        for (word, leaf) in leaves {
+            let reversed = word.chars().rev().collect::<String>();
+            suffixes.insert(reversed.chars(), leaf.display());
+        }
+        self.suffixes = Some(suffixes);
+    }
+
+    /// Builds an index of every suffix of every word in the tree, which enables
+    /// [CompletionTree::search_substring]. The index is kept up to date on insert and removal
+    /// once built.
+    ///
+    /// Each word is indexed once for every character it contains, so the memory used grows
+    /// with the square of the word lengths and can be many times that of the tree itself. The
+    /// index isn't included in [CompletionTree::memory_bytes].
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("acrobat batman");
+    /// completions.build_substring_index();
+    /// completions.insert("robin");
+    /// assert_eq!(
+    ///     completions.search_substring("ob"),
+    ///     vec!["acrobat".to_string(), "robin".to_string()]);
+    /// ```
+    pub fn build_substring_index(&mut self) {
+        let mut index = SubstringIndex::default();
+        let mut leaves = vec![];
+        self.root.collect_leaves("".to_string(), &mut leaves);
+        for (stored, leaf) in leaves {
+            let word = Arc::from(leaf.display().unwrap_or(&stored));
+            index_substrings(&mut index, &stored, &word);
+        }
+        self.substrings = Some(index);
+    }
+
+    /// Returns all words containing `needle`, sorted alphabetically. The needle is matched
+    /// like completions are, so it's lowercased in case insensitive trees. Returns an empty
+    /// vector if no words match or the substring index hasn't been built using
+    /// [CompletionTree::build_substring_index].
+    ///
+    /// # Arguments
+    ///
+    /// * `needle`  The substring to search for
+    pub fn search_substring(&self, needle: &str) -> Vec<String> {
+        let index = match &self.substrings {
+            Some(index) => index,
+            None => return vec![],
+        };
+        let words = index
+            .values_with_prefix(&self.fold(needle))
+            .into_iter()
+            .flatten()
+            .collect::<BTreeSet<&Arc<str>>>();
+        words.into_iter().map(|word| word.to_string()).collect()
+    }
+
+    /// Returns true if the segments of the words in the tree are indexed for
+    /// [CompletionTree::search_infix]
+    pub fn infix_indexing(&self) -> bool {
+        self.infixes.is_some()
+    }
+
+    /// Enables or disables the infix index, which enables [CompletionTree::search_infix].
+    /// Enabling it indexes the words already in the tree, after which the index is kept up to
+    /// date on insert and removal. Disabling it drops the index.
+    ///
+    /// A word is split into segments on camelCase humps, `_` and `-`, and indexed once for
+    /// every segment under the rest of the word starting at that segment. "db_connection_pool"
+    /// is indexed under "db_connection_pool", "connection_pool" and "pool". The memory used
+    /// grows with the amount of segments per word, which is far less than the substring index
+    /// of [CompletionTree::build_substring_index] that indexes every character. The index
+    /// isn't included in [CompletionTree::memory_bytes].
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::with_inclusions(&['_']);
+    /// completions.insert("db_connection_pool openConnection");
+    /// completions.set_infix_indexing(true);
+    /// assert_eq!(
+    ///     completions.search_infix("conn"),
+    ///     vec!["db_connection_pool".to_string(), "openConnection".to_string()]);
+    /// ```
+    pub fn set_infix_indexing(&mut self, enabled: bool) {
+        if !enabled {
+            self.infixes = None;
+        } else if self.infixes.is_none() {
+            self.build_infix_index();
+        }
+    }
+
+    fn build_infix_index(&mut self) {
+        let mut index = SubstringIndex::default();
+        let mut leaves = vec![];
+        self.root.collect_leaves("".to_string(), &mut leaves);
+        for (stored, leaf) in leaves {
+            index_infixes(&mut index, &Arc::from(leaf.display().unwrap_or(&stored)));
+        }
+        self.infixes = Some(index);
+    }
+
+    /// Returns all words with a segment starting with `needle`, sorted alphabetically and
+    /// without duplicates, see [CompletionTree::set_infix_indexing]. Segments are matched
+    /// ignoring case, so "conn" finds "openConnection". Returns an empty vector if no words
+    /// match or the infix index isn't enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `needle`  The start of the segment to search for
+    pub fn search_infix(&self, needle: &str) -> Vec<String> {
+        let index = match &self.infixes {
+            Some(index) => index,
+            None => return vec![],
+        };
+        let words = index
+            .values_with_prefix(&needle.to_lowercase())
+            .into_iter()
+            .flatten()
+            .collect::<BTreeSet<&Arc<str>>>();
+        words.into_iter().map(|word| word.to_string()).collect()
+    }
+
+    /// Returns all words ending with `suffix`, sorted by [CompletionTree::sort_order]. Returns
+    /// None if no words match or the suffix index hasn't been built using
+    /// [CompletionTree::build_suffix_index].
+    ///
+    /// # Arguments
+    ///
+    /// * `suffix`  The suffix to match
+    pub fn complete_suffix(&self, suffix: &str) -> Option<Vec<String>> {
+        let reversed = self.fold(suffix).chars().rev().collect::<String>();
+        let node = self.suffixes.as_ref()?.find(reversed.chars())?;
+        let mut leaves = vec![];
+        node.collect_leaves("".to_string(), &mut leaves);
+        let mut words = leaves
+            .into_iter()
+            .map(|(ext, leaf)| match leaf.display() {
+                Some(display) => display.to_string(),
+                None => ext.chars().rev().chain(reversed.chars().rev()).collect(),
+            })
+            .collect::<Vec<String>>();
+        if words.is_empty() {
+            return None;
+        }
+        words.sort_by(|a, b| self.sort_order.compare(a, b));
+        Some(words)
+    }
+
+    /// Returns all words matching the glob `pattern`, sorted by [CompletionTree::sort_order].
+    /// `?` matches any single character and `*` any run of characters, including none. Other
+    /// characters match literally and are folded like completions are.
+    ///
+    /// Like [CompletionTree::complete] the pattern only has to match the start of a word, as
+    /// if it ended with `*`, so "ba?" matches "batman". A leading `*` lets the match start
+    /// anywhere in the word, which visits the whole tree. Returns None if nothing matches.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` The pattern to match
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// assert_eq!(
+    ///     completions.complete_glob("ba?m*"),
+    ///     Some(vec!["batman".to_string(), "batmobile".to_string()]));
+    /// assert_eq!(
+    ///     completions.complete_glob("*bile"),
+    ///     Some(vec!["batmobile".to_string()]));
+    /// ```
+    pub fn complete_glob(&self, pattern: &str) -> Option<Vec<String>> {
+        let pattern = self.fold(pattern).chars().collect::<Vec<char>>();
+        let mut matches = vec![];
+        self.root.match_glob(
+            &pattern,
+            &mut String::new(),
+            &mut BTreeSet::new(),
+            &mut matches,
+        );
+        // Every word below a matching node matches, so nodes below another match are skipped.
+        // Sorted by path such nodes directly follow the match above them.
+        matches.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut leaves = vec![];
+        let mut covered: Option<String> = None;
+        for (path, node) in matches {
+            if covered
+                .as_ref()
+                .is_some_and(|covered| path.starts_with(covered.as_str()))
+            {
+                continue;
+            }
+            node.collect_leaves(path.clone(), &mut leaves);
+            covered = Some(path);
+        }
+        let mut words = leaves
+            .into_iter()
+            .map(|(word, leaf)| leaf.display().map(String::from).unwrap_or(word))
+            .collect::<Vec<String>>();
+        if words.is_empty() {
+            return None;
+        }
+        words.sort_by(|a, b| self.sort_order.compare(a, b));
+        Some(words)
+    }
+
+    /// Removes a single word from the completion tree. Nodes that are no longer part of any
+    /// word are pruned so the size of the tree shrinks accordingly.
+    /// Returns true if the word existed and was removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `word`    The word to remove
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman batmobile");
+    /// assert!(completions.remove("batman"));
+    /// assert!(!completions.remove("batman"));
+    /// assert_eq!(
+    ///     completions.complete("bat"),
+    ///     Some(vec!["batmobile".to_string()]));
+    /// ```
+    pub fn remove(&mut self, word: &str) -> bool {
+        let path = self.fold(word);
+        let previous = if self.substrings.is_some() || self.infixes.is_some() {
+            self.stored_word(&path)
+        } else {
+            None
+        };
+        let removed = self.root.remove(path.chars());
+        if !removed {
+            return false;
+        }
+        self.word_count -= 1;
+        if let Some(suffixes) = &mut self.suffixes {
+            suffixes.remove(path.chars().rev().collect::<String>().chars());
+        }
+        if let Some(recency) = &mut self.recency {
+            recency.remove(&path);
+        }
+        if let Some(order) = &mut self.insert_order {
+            order.remove(path.as_ref());
+        }
+        if let Some(previous) = previous {
+            if let Some(index) = &mut self.substrings {
+                unindex_substrings(index, &path, &previous);
+            }
+            if let Some(index) = &mut self.infixes {
+                unindex_infixes(index, &previous);
+            }
+        }
+        true
+    }
+
+    /// Removes all words starting with `prefix`, including the prefix itself if it's a word,
+    /// and prunes nodes that are no longer part of any word. Returns the number of removed
+    /// words.
+    ///
+    /// The prefix is walked character by character, so prefixes containing characters that
+    /// aren't accepted by the tree never match. To remove a namespace like `debug.` the tree
+    /// needs `.` in its inclusions, see [CompletionTree::with_inclusions].
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix`  The prefix of the words to remove
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::with_inclusions(&['.']);
+    /// completions.insert("debug.trace debug.break config.debug");
+    /// assert_eq!(completions.remove_prefix("debug."), 2);
+    /// assert_eq!(completions.words(), vec!["config.debug".to_string()]);
+    /// ```
+    pub fn remove_prefix(&mut self, prefix: &str) -> u32 {
+        let removed = self.root.remove_prefix(self.fold(prefix).chars());
+        self.refresh_indexes(removed);
+        removed
+    }
+
+    /// Returns true if the exact word exists in the completion tree. Words that are only
+    /// prefixes of inserted words are not considered to be contained. Like
+    /// [CompletionTree::insert] and [CompletionTree::complete] this accepts any string type.
+    ///
+    /// # Arguments
+    ///
+    /// * `word`    The word to look for
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("dumpsterfire");
+    /// assert!(completions.contains("dumpsterfire"));
+    /// assert!(!completions.contains("dumpster"));
+    /// ```
+    pub fn contains(&self, word: impl AsRef<str>) -> bool {
+        self.root
+            .find(self.fold(word.as_ref()).chars())
+            .is_some_and(|node| node.is_leaf())
+    }
+
+    /// Returns the longest word in the tree that `line` starts with, which allows input to be
+    /// split into known words by repeatedly taking the longest match. Returns None if no word
+    /// is a prefix of `line`.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The text to match words against
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_min_word_len(3);
+    /// completions.insert("bat batman");
+    /// assert_eq!(completions.longest_match("batmans"), Some("batman".to_string()));
+    /// assert_eq!(completions.longest_match("batmo"), Some("bat".to_string()));
+    /// assert_eq!(completions.longest_match("ba"), None);
+    /// ```
+    pub fn longest_match(&self, line: &str) -> Option<String> {
+        let folded = self.fold(line);
+        let mut node = &self.root;
+        let mut longest = None;
+        for (i, c) in folded.char_indices() {
+            node = match node.subnodes.get(&c) {
+                Some(subnode) => subnode,
+                None => break,
+            };
+            if node.is_leaf() {
+                longest = Some((i + c.len_utf8(), node));
+            }
+        }
+        let (end, leaf) = longest?;
+        Some(
+            leaf.display()
+                .map(String::from)
+                .unwrap_or_else(|| folded[..end].to_string()),
+        )
+    }
+
+    /// Inserts all the words of `other` into this tree. The words are inserted using the
+    /// configuration of this tree so words not fulfilling its rules, like
+    /// [CompletionTree::min_word_len], are skipped. Words existing in both trees are only
+    /// stored once.
+    ///
+    /// # Arguments
+    ///
+    /// * `other`   The tree to merge words from
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin");
+    /// let mut other = CompletionTree::default();
+    /// other.insert("robin batmobile");
+    /// completions.merge(&other);
+    /// assert_eq!(completions.word_count(), 3);
+    /// ```
+    pub fn merge(&mut self, other: &CompletionTree) {
+        other.words().iter().for_each(|word| self.insert_word(word));
+    }
+
+    /// Returns a new tree holding the words of this tree that aren't in `other`. The new tree
+    /// has the configuration of this tree and keeps the insertion counts of its words. Words
+    /// are looked up in `other` using [CompletionTree::contains], so they're matched using
+    /// the configuration of `other`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other`   The tree holding the words to leave out
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut allowed = CompletionTree::default();
+    /// allowed.insert("commit checkout stash");
+    /// let mut deprecated = CompletionTree::default();
+    /// deprecated.insert("stash");
+    /// assert_eq!(
+    ///     allowed.difference(&deprecated).words(),
+    ///     vec!["checkout".to_string(), "commit".to_string()]);
+    /// ```
+    pub fn difference(&self, other: &CompletionTree) -> CompletionTree {
+        let mut tree = self.clone();
+        tree.retain(|word| !other.contains(word));
+        tree
+    }
+
+    /// Returns a new tree holding the words of this tree that are also in `other`. Like
+    /// [CompletionTree::difference] the new tree has the configuration of this tree and
+    /// words are looked up in `other` using [CompletionTree::contains].
+    ///
+    /// # Arguments
+    ///
+    /// * `other`   The tree holding the words to keep
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut allowed = CompletionTree::default();
+    /// allowed.insert("commit checkout stash");
+    /// let mut deprecated = CompletionTree::default();
+    /// deprecated.insert("stash rebase");
+    /// assert_eq!(allowed.intersection(&deprecated).words(), vec!["stash".to_string()]);
+    /// ```
+    pub fn intersection(&self, other: &CompletionTree) -> CompletionTree {
+        let mut tree = self.clone();
+        tree.retain(|word| other.contains(word));
+        tree
+    }
+
+    /// Multiplies the insertion count of every word by `factor`, rounding down. Calling this
+    /// periodically weights the counts used by [CompletionTree::complete_ranked] and
+    /// [CompletionTree::top_k] towards recent insertions. The factor is clamped to the range
+    /// 0.0 to 1.0 so counts never grow.
+    ///
+    /// Words are kept when their count reaches zero, they only rank last. Use
+    /// [CompletionTree::decay_and_prune] to remove them as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `factor`  The factor to multiply counts by
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert_with_count("batman", 10);
+    /// completions.insert("robin");
+    /// completions.decay(0.5);
+    /// assert_eq!(completions.prune_below(1), 1);
+    /// assert_eq!(
+    ///     completions.iter_counts().collect::<Vec<(String, u32)>>(),
+    ///     vec![("batman".to_string(), 5)]);
+    /// ```
+    pub fn decay(&mut self, factor: f32) {
+        let factor = if factor.is_nan() {
+            0.0
+        } else {
+            factor.clamp(0.0, 1.0)
+        };
+        self.root.scale_counts(f64::from(factor));
+    }
+
+    /// Works like [CompletionTree::decay] but also removes the words whose count reaches
+    /// zero. Returns the number of removed words.
+    ///
+    /// # Arguments
+    ///
+    /// * `factor`  The factor to multiply counts by
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert_with_count("batman", 10);
+    /// completions.insert("robin");
+    /// assert_eq!(completions.decay_and_prune(0.5), 1);
+    /// assert_eq!(completions.words(), vec!["batman".to_string()]);
+    /// ```
+    pub fn decay_and_prune(&mut self, factor: f32) -> u32 {
+        self.decay(factor);
+        self.prune_below(1)
+    }
+
+    /// Removes all words that have been inserted fewer than `min_count` times. Nodes that are
+    /// no longer part of any word are pruned. Returns the number of removed words.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_count`   The minimum insertion count a word needs to be kept
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batman");
+    /// assert_eq!(completions.prune_below(2), 2);
+    /// assert_eq!(completions.words(), vec!["batman".to_string()]);
+    /// ```
+    pub fn prune_below(&mut self, min_count: u32) -> u32 {
+        let removed = self
+            .root
+            .retain_leaves(&mut "".to_string(), &mut |_, leaf| {
+                leaf.count() >= min_count
+            });
+        self.refresh_indexes(removed);
+        removed
+    }
+
+    /// Removes all words for which `f` returns false. Each word is passed in the form it is
+    /// completed to. Nodes that are no longer part of any word are pruned. Returns the number
+    /// of removed words.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`   The predicate deciding which words to keep
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile");
+    /// assert_eq!(completions.retain(|word| word.starts_with("bat")), 1);
+    /// assert_eq!(completions.words(), vec!["batman".to_string(), "batmobile".to_string()]);
+    /// ```
+    pub fn retain<F: FnMut(&str) -> bool>(&mut self, mut f: F) -> u32 {
+        let removed = self
+            .root
+            .retain_leaves(&mut "".to_string(), &mut |path, leaf| {
+                f(leaf.display().unwrap_or(path))
+            });
+        self.refresh_indexes(removed);
+        removed
+    }
+
+    /// Rebuilds secondary indexes after `removed` words were removed in bulk
+    fn refresh_indexes(&mut self, removed: u32) {
+        self.word_count -= removed;
+        if removed > 0 && self.suffixes.is_some() {
+            self.build_suffix_index();
+        }
+        if removed > 0 && self.substrings.is_some() {
+            self.build_substring_index();
+        }
+        if removed > 0 && self.infixes.is_some() {
+            self.build_infix_index();
+        }
+        if let (true, Some(order)) = (removed > 0, &mut self.insert_order) {
+            let root = &self.root;
+            order.retain(|word, _| root.find(word.chars()).is_some_and(|node| node.is_leaf()));
+        }
+        if removed > 0 {
+            self.sync_recency();
+        }
+    }
+
+    /// Changes the word separator used by CompletionTree::insert()
+    /// If left unchanged the default is [WordSeparator::Whitespace]
+    ///
+    /// # Arguments
+    ///
+    /// * `separator`   A WordSeparator
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::{CompletionTree, WordSeparator};
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.separator(WordSeparator::Separator("|"));
+    /// completions.set_min_word_len(1);
+    ///
+    /// // Insert multiple words
+    /// completions.insert("a|line|with|many|words");
+    /// assert_eq!(completions.word_count(), 5);
+    /// completions.clear();
+    /// assert_eq!(completions.word_count(), 0);
+    ///
+    /// // The above line is equal to the following:
+    /// completions.insert("a");
+    /// completions.insert("line");
+    /// completions.insert("with");
+    /// completions.insert("many");
+    /// completions.insert("words");
+    /// assert_eq!(completions.word_count(), 5);
+    /// ```
+    pub fn separator(&mut self, separator: WordSeparator) {
+        self.separator = separator;
+    }
+
+    /// Sets a function used by [CompletionTree::insert] to split lines into words, in place
+    /// of the configured [WordSeparator]. The returned words must be slices of the line
+    /// passed to the function, they can't borrow from anything else or be built on the fly.
+    ///
+    /// Completion still uses the [WordSeparator] to find the last word of a line. The
+    /// splitter isn't included when the tree is serialized.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`   The function splitting a line into words
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_splitter(|line| line.split(|c: char| c.is_ascii_punctuation()).collect());
+    /// completions.insert("batman.robin!batmobile");
+    /// assert_eq!(completions.word_count(), 3);
+    /// ```
+    pub fn set_splitter<F>(&mut self, f: F)
+    where
+        F: Fn(&str) -> Vec<&str> + Send + Sync + 'static,
+    {
+        self.splitter = Some(Splitter(Arc::new(f)));
+    }
+
+    /// Returns an optional vector of completions based on the provided input, in the order
+    /// set using [CompletionTree::set_sort_order]. The line can be any string type, like
+    /// `&str`, `String` or `Cow<str>`, so owned queries can be passed without keeping a borrow
+    /// alive.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed. Words are separated
+    ///   using the configured [WordSeparator]
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// assert_eq!(
+    ///     completions.complete("bat"),
+    ///     Some(vec!["batcave", "batman", "batmobile"].iter().map(|s| s.to_string()).collect()));
+    ///
+    /// assert_eq!(
+    ///     completions.complete("to the bat"),
+    ///     Some(vec!["to the batcave", "to the batman", "to the batmobile"].iter().map(|s| s.to_string()).collect()));
+    /// ```
+    pub fn complete(&self, line: impl AsRef<str>) -> Option<Vec<String>> {
+        let order = self.sort_order;
+        self.complete_by(line.as_ref(), |a, b| order.compare(a, b))
+    }
+
+    /// Returns an optional [CompleteResult] holding the completions [CompletionTree::complete]
+    /// returns together with whether the completed word is itself a word in the tree. This
+    /// tells input that's already a complete word apart from input that's only a prefix
+    /// without a separate call to [CompletionTree::contains].
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("robin robinson");
+    /// let result = completions.complete_detailed("robin").unwrap();
+    /// assert!(result.exact);
+    /// assert_eq!(result.completions, vec!["robin".to_string(), "robinson".to_string()]);
+    /// assert!(!completions.complete_detailed("rob").unwrap().exact);
+    /// ```
+    pub fn complete_detailed(&self, line: &str) -> Option<CompleteResult> {
+        let completions = self.complete(line)?;
+        let exact = self
+            .find_last_word(line)
+            .is_some_and(|(_, node)| node.is_leaf());
+        Some(CompleteResult { exact, completions })
+    }
+
+    /// Returns an optional vector of completions based on the provided input, ordered using
+    /// the provided comparator instead of alphabetically.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    /// * `cmp`     The comparator used to order the completions
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batmobile batman batcave");
+    /// assert_eq!(
+    ///     completions.complete_by("bat", |a, b| b.cmp(a)),
+    ///     Some(vec!["batmobile", "batman", "batcave"].iter().map(|s| s.to_string()).collect()));
+    /// ```
+    pub fn complete_by<F>(&self, line: &str, cmp: F) -> Option<Vec<String>>
+    where
+        F: FnMut(&String, &String) -> Ordering,
+    {
+        let (last_word, targets, node) = self.find_completions(line)?;
+        let leaves = self.capped_leaves(node);
+        let completions =
+            self.finish_completions(line, last_word, &targets, &leaves, |c| c.sort_by(cmp))?;
+        self.mark_used(last_word, leaves.iter().map(|(ext, _)| ext.as_str()));
+        Some(completions)
+    }
+
+    /// Collects the leaves below `node`, stopping one past [CompletionTree::max_results]
+    fn capped_leaves<'a>(
+        &self,
+        node: Option<&'a CompletionNode>,
+    ) -> Vec<(String, &'a CompletionNode)> {
+        match (node, self.max_results) {
+            // At most one word past the cap is visited
+            (node, Some(cap)) => Leaves::new(node)
+                .take(cap.saturating_add(1))
+                .collect::<Vec<_>>(),
+            (Some(node), None) => {
+                let mut leaves = vec![];
+                node.collect_leaves("".to_string(), &mut leaves);
+                leaves
+            }
+            (None, None) => vec![],
+        }
+    }
+
+    /// Turns the `leaves` found below `last_word` into completions of `line` like
+    /// [CompletionTree::complete] does. Returns None if there are more leaves than
+    /// [CompletionTree::max_results]. The completions are ordered using `order`, after the
+    /// expansions of the alias `targets`, and completions equal to an expansion are dropped.
+    fn finish_completions<F>(
+        &self,
+        line: &str,
+        last_word: &str,
+        targets: &[&str],
+        leaves: &[(String, &CompletionNode)],
+        order: F,
+    ) -> Option<Vec<String>>
+    where
+        F: FnOnce(&mut Vec<String>),
+    {
+        if self.max_results.is_some_and(|cap| leaves.len() > cap) {
+            return None;
+        }
+        let mut completions = leaves
+            .iter()
+            .map(|(ext, leaf)| completion(line, last_word, ext, leaf))
+            .collect::<Vec<String>>();
+        order(&mut completions);
+        let mut completions = expansions(line, last_word, targets)
+            .into_iter()
+            .chain(completions)
+            .collect();
+        dedup_completions(&mut completions);
+        Some(completions)
+    }
+
+    /// Adds an alias expanding to `target`, so completing "btm", or a prefix of it, can return
+    /// "batmobile". Expansions are matched like words and returned before the words of the
+    /// tree, but they aren't words themselves, so they aren't counted or removed by
+    /// [CompletionTree::clear].
+    ///
+    /// # Arguments
+    ///
+    /// * `alias`   The abbreviation to expand
+    /// * `target`  The text the alias expands to
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman btmagazine");
+    /// completions.add_alias("btm", "batmobile");
+    /// assert_eq!(
+    ///     completions.complete("to the bt"),
+    ///     Some(vec!["to the batmobile".to_string(), "to the btmagazine".to_string()]));
+    /// ```
+    pub fn add_alias(&mut self, alias: &str, target: &str) {
+        let alias = self.fold(alias).into_owned();
+        self.aliases
+            .entry(alias)
+            .or_default()
+            .insert(target.to_string());
+    }
+
+    /// Removes an alias and all of its targets, see [CompletionTree::add_alias]. Returns true
+    /// if the alias existed.
+    ///
+    /// # Arguments
+    ///
+    /// * `alias`   The alias to remove
+    pub fn remove_alias(&mut self, alias: &str) -> bool {
+        self.aliases.remove(self.fold(alias).as_ref()).is_some()
+    }
+
+    /// Returns the targets of the aliases starting with `last_word`, ordered by alias and then
+    /// target
+    fn alias_targets(&self, last_word: &str) -> Vec<&str> {
+        if self.aliases.is_empty() {
+            return vec![];
+        }
+        let prefix = self.fold(last_word);
+        self.aliases
+            .range::<str, _>((Bound::Included(prefix.as_ref()), Bound::Unbounded))
+            .take_while(|(alias, _)| alias.starts_with(prefix.as_ref()))
+            .flat_map(|(_, targets)| targets)
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Returns an optional vector of completions based on the provided input, keeping only
+    /// words for which `keep` returns true. `keep` is called with each completed word, without
+    /// the preceding part of the line, and rejected words are skipped before any completion
+    /// is built. Like [CompletionTree::complete] this returns None, rather than an empty
+    /// vector, when there are no completions left.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    /// * `keep`    The predicate deciding which words to complete to
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// let line = "batman and bat";
+    /// assert_eq!(
+    ///     completions.complete_filtered(line, |word| !line.contains(word)),
+    ///     Some(vec!["batman and batcave".to_string(), "batman and batmobile".to_string()]));
+    /// assert_eq!(completions.complete_filtered("rob", |_| false), None);
+    /// ```
+    pub fn complete_filtered<F>(&self, line: &str, mut keep: F) -> Option<Vec<String>>
+    where
+        F: FnMut(&str) -> bool,
+    {
+        let (last_word, mut targets, node) = self.find_completions(line)?;
+        targets.retain(|target| keep(target));
+        let leaves = Leaves::new(node)
+            .filter(|(ext, leaf)| keep(&completed_word(last_word, ext, leaf)))
+            .collect::<Vec<_>>();
+        self.mark_used(last_word, leaves.iter().map(|(ext, _)| ext.as_str()));
+        let mut completions = leaves
+            .iter()
+            .map(|(ext, leaf)| completion(line, last_word, ext, leaf))
+            .collect::<Vec<String>>();
+        completions.sort_by(|a, b| self.sort_order.compare(a, b));
+        let mut completions = expansions(line, last_word, &targets)
+            .into_iter()
+            .chain(completions)
+            .collect::<Vec<String>>();
+        if completions.is_empty() {
+            return None;
+        }
+        dedup_completions(&mut completions);
+        Some(completions)
+    }
+
+    /// Returns an optional vector of completions based on the provided input, matching the
+    /// last word ignoring case for this call only. The tree itself is unchanged, so a case
+    /// sensitive tree can be queried case insensitively without rebuilding it. Matched words
+    /// replace the last word in the form they are stored.
+    ///
+    /// Since the stored words keep their case, every character of the input is matched against
+    /// both case forms in the tree, and each level where both exist doubles the number of
+    /// branches walked. An input of n characters visits up to 2^n prefixes, though mixed case
+    /// trees rarely branch on more than the first few characters. Prefer
+    /// [CompletionTree::set_case_insensitive] if every query ignores case.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("Batman battery robin");
+    /// assert_eq!(completions.complete("bat"), Some(vec!["battery".to_string()]));
+    /// assert_eq!(
+    ///     completions.complete_ignore_case("to the bat"),
+    ///     Some(vec!["to the Batman".to_string(), "to the battery".to_string()]));
+    /// ```
+    pub fn complete_ignore_case(&self, line: &str) -> Option<Vec<String>> {
+        let last_word = self.completable_word(line)?;
+        let target = self.fold(last_word).chars().collect::<Vec<char>>();
+        let mut nodes = vec![];
+        self.root
+            .find_ignore_case(&target, "".to_string(), &mut nodes);
+        let head = line.strip_suffix(last_word).unwrap_or(line);
+        let mut completions = vec![];
+        for (prefix, node) in nodes {
+            let mut leaves = vec![];
+            node.collect_leaves(prefix, &mut leaves);
+            completions.extend(leaves.into_iter().map(|(word, leaf)| match leaf.display() {
+                Some(display) => format!("{}{}", head, display),
+                None => format!("{}{}", head, word),
+            }));
+        }
+        if completions.is_empty() {
+            return None;
+        }
+        completions.sort_by(|a, b| self.sort_order.compare(a, b));
+        dedup_completions(&mut completions);
+        Some(completions)
+    }
+
+    /// Returns an iterator lazily yielding completions based on the provided input. Nodes are
+    /// only visited as the iterator advances so no completions are collected up front.
+    /// Completions are yielded in the order they are stored in the tree, which is alphabetical
+    /// unless [CompletionTree::set_case_insensitive] is used. The iterator is empty if there
+    /// are no completions.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// let mut iter = completions.complete_iter("to the bat");
+    /// assert_eq!(iter.next(), Some("to the batcave".to_string()));
+    /// assert_eq!(iter.next(), Some("to the batman".to_string()));
+    /// assert_eq!(completions.complete_iter("joker").next(), None);
+    /// ```
+    pub fn complete_iter<'a>(&'a self, line: &str) -> impl Iterator<Item = String> + 'a {
+        let (last_word, expansions, leaves) = match self.find_completions(line) {
+            Some((last_word, targets, node)) => (
+                last_word.to_string(),
+                expansions(line, last_word, &targets),
+                Leaves::new(node),
+            ),
+            None => ("".to_string(), vec![], Leaves::new(None)),
+        };
+        let line = line.to_string();
+        let completions = leaves.filter_map({
+            let expansions = expansions.clone();
+            move |(ext, leaf)| {
+                let completion = completion(&line, &last_word, &ext, leaf);
+                // Completions already yielded as an alias expansion are skipped
+                if expansions.contains(&completion) {
+                    return None;
+                }
+                self.mark_used(&last_word, Some(ext.as_str()));
+                Some(completion)
+            }
+        });
+        expansions.into_iter().chain(completions)
+    }
+
+    /// Calls `f` with each completion based on the provided input as it's found while walking
+    /// the tree. Completions are built in a single reused buffer, so no vector is allocated
+    /// and, unless words are stored in a display form, no string is allocated per completion.
+    /// Completions are passed in the order they are stored in the tree, which is alphabetical
+    /// unless [CompletionTree::set_case_insensitive] is used. Returns true if there were any
+    /// completions.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    /// * `f`       The function called with each completion
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile");
+    /// let mut found = vec![];
+    /// assert!(completions.complete_each("to the bat", |c| found.push(c.to_string())));
+    /// assert_eq!(found, vec!["to the batman".to_string(), "to the batmobile".to_string()]);
+    /// assert!(!completions.complete_each("joker", |_| {}));
+    /// ```
+    pub fn complete_each<F: FnMut(&str)>(&self, line: &str, mut f: F) -> bool {
+        let (last_word, targets, node) = match self.find_completions(line) {
+            Some(found) => found,
+            None => return false,
+        };
+        let expansions = expansions(line, last_word, &targets);
+        expansions.iter().for_each(|expansion| f(expansion));
+        let node = match node {
+            Some(node) => node,
+            None => return true,
+        };
+        let head = line.strip_suffix(last_word).unwrap_or(line);
+        let mut found = !expansions.is_empty();
+        node.visit_leaves(&mut line.to_string(), &mut |path, leaf| {
+            let completion = match leaf.display() {
+                Some(display) => Cow::Owned(format!("{}{}", head, display)),
+                None => Cow::Borrowed(path),
+            };
+            // Completions already passed as an alias expansion are skipped
+            if expansions.iter().any(|expansion| *expansion == completion) {
+                return;
+            }
+            found = true;
+            self.mark_used(last_word, path.get(line.len()..));
+            f(&completion);
+        });
+        found
+    }
+
+    /// Returns the first completion based on the provided input, which is the first entry
+    /// [CompletionTree::complete] would return if it wasn't limited by
+    /// [CompletionTree::set_max_results]. An alias expansion comes first, see
+    /// [CompletionTree::add_alias]. Returns None if nothing matches.
+    ///
+    /// With [SortOrder::Alphabetical] and words stored as inserted the tree is walked in order
+    /// and the walk stops at the first word found. Otherwise, like with another
+    /// [CompletionTree::sort_order] or [CompletionTree::set_case_insensitive], every completion
+    /// is built to find the first one, but none are collected or sorted.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// assert_eq!(
+    ///     completions.first_completion("to the bat"),
+    ///     Some("to the batcave".to_string()));
+    /// assert_eq!(completions.first_completion("joker"), None);
+    /// ```
+    pub fn first_completion(&self, line: &str) -> Option<String> {
+        let (last_word, targets, node) = self.find_completions(line)?;
+        if let Some(expansion) = expansions(line, last_word, &targets).into_iter().next() {
+            return Some(expansion);
+        }
+        let mut leaves = Leaves::new(node);
+        // Display forms can order differently than the paths they are stored at
+        let stored_order = self.sort_order == SortOrder::Alphabetical
+            && !self.case_insensitive
+            && !self.fold_diacritics;
+        let (ext, completion) = if stored_order {
+            let (ext, leaf) = leaves.next()?;
+            let completion = completion(line, last_word, &ext, leaf);
+            (ext, completion)
+        } else {
+            leaves
+                .map(|(ext, leaf)| {
+                    let completion = completion(line, last_word, &ext, leaf);
+                    (ext, completion)
+                })
+                .min_by(|(_, a), (_, b)| self.sort_order.compare(a, b))?
+        };
+        self.mark_used(last_word, Some(ext.as_str()));
+        Some(completion)
+    }
+
+    /// Returns an optional vector of completed words based on the provided input. Unlike
+    /// [CompletionTree::complete] only the completed last word is returned, without the
+    /// preceding part of the line.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// assert_eq!(
+    ///     completions.complete_words("to the bat"),
+    ///     Some(vec!["batcave", "batman", "batmobile"].iter().map(|s| s.to_string()).collect()));
+    /// ```
+    pub fn complete_words(&self, line: &str) -> Option<Vec<String>> {
+        let (last_word, targets, node) = self.find_completions(line)?;
+        let leaves = Leaves::new(node).collect::<Vec<_>>();
+        self.mark_used(last_word, leaves.iter().map(|(ext, _)| ext.as_str()));
+        let mut words = leaves
+            .iter()
+            .map(|(ext, leaf)| completed_word(last_word, ext, leaf))
+            .collect::<Vec<String>>();
+        words.sort_by(|a, b| self.sort_order.compare(a, b));
+        let mut words = targets.into_iter().map(String::from).chain(words).collect();
+        dedup_completions(&mut words);
+        Some(words)
+    }
+
+    /// Returns an optional vector of the text each completion would append to the provided
+    /// input, like the ghost text shown after a cursor. These are the completions
+    /// [CompletionTree::complete] returns, in the same order, without the input. A word
+    /// matching the last word exactly extends it by an empty string. Alias expansions and
+    /// words completed in a stored display form, like in case insensitive trees, extend the
+    /// input by the part following as many characters as the last word has.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile");
+    /// assert_eq!(
+    ///     completions.complete_extensions("to the bat"),
+    ///     Some(vec!["man".to_string(), "mobile".to_string()]));
+    /// ```
+    pub fn complete_extensions(&self, line: &str) -> Option<Vec<String>> {
+        let completions = self.complete(line)?;
+        let last_word = self.completable_word(line)?;
+        // Every completion starts with the line up to the last word
+        let head = line.len() - last_word.len();
+        let matched = last_word.chars().count();
+        Some(
+            completions
+                .into_iter()
+                .map(|completion| completion[head..].chars().skip(matched).collect())
+                .collect(),
+        )
+    }
+
+    /// Returns an optional vector of completions for the last `n` whitespace separated words
+    /// of the provided input, completed as a single phrase. The words are joined using a
+    /// single space before being looked up. If the line has fewer than `n` words all of them
+    /// are used. Returns None if `n` is 0 or the line holds no words. Aliases and
+    /// [CompletionTree::max_results] apply to the phrase like they do in
+    /// [CompletionTree::complete].
+    ///
+    /// Phrases can only be stored if spaces are accepted as in-word characters, so the tree
+    /// needs to be created using `with_inclusions(&[' '])` and a [WordSeparator] other than
+    /// [WordSeparator::Whitespace] to insert phrases with embedded spaces.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    /// * `n`       The amount of trailing words to complete as a phrase
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::{CompletionTree, WordSeparator};
+    ///
+    /// let mut completions = CompletionTree::with_inclusions(&[' ']);
+    /// completions.separator(WordSeparator::Separator(","));
+    /// completions.insert("cast fire bolt,cast frost nova");
+    /// assert_eq!(
+    ///     completions.complete_phrase("now cast fire", 2),
+    ///     Some(vec!["now cast fire bolt".to_string()]));
+    /// ```
+    pub fn complete_phrase(&self, line: &str, n: usize) -> Option<Vec<String>> {
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        let words = &words[words.len().saturating_sub(n)..];
+        let first = words.first()?;
+        let fragment = words.join(" ");
+        if self.char_len(&fragment) < self.min_completion_len {
+            return None;
+        }
+        let targets = self.alias_targets(&fragment);
+        let node = self.root.find(self.fold(&fragment).chars());
+        if targets.is_empty() && node.is_none() {
+            return None;
+        }
+        let start = first.as_ptr() as usize - line.as_ptr() as usize;
+        let line = format!("{}{}", &line[..start], fragment);
+        let leaves = self.capped_leaves(node);
+        let order = self.sort_order;
+        let completions = self.finish_completions(&line, &fragment, &targets, &leaves, |c| {
+            c.sort_by(|a, b| order.compare(a, b))
+        })?;
+        self.mark_used(&fragment, leaves.iter().map(|(ext, _)| ext.as_str()));
+        Some(completions)
+    }
+
+    /// Returns an optional vector of completed words based on the provided input, like
+    /// [CompletionTree::complete_words], together with the byte range of each word matched by
+    /// the input. This allows the typed part of a completion to be highlighted.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile");
+    /// assert_eq!(
+    ///     completions.complete_with_spans("to the batm"),
+    ///     Some(vec![("batman".to_string(), 0..4), ("batmobile".to_string(), 0..4)]));
+    /// ```
+    pub fn complete_with_spans(&self, line: &str) -> Option<Vec<(String, Range<usize>)>> {
+        let (last_word, targets, node) = self.find_completions(line)?;
+        let matched = last_word.chars().count();
+        let leaves = Leaves::new(node).collect::<Vec<_>>();
+        self.mark_used(last_word, leaves.iter().map(|(ext, _)| ext.as_str()));
+        let mut words = leaves
+            .iter()
+            .map(|(ext, leaf)| {
+                let word = completed_word(last_word, ext, leaf);
+                let end = word
+                    .char_indices()
+                    .nth(matched)
+                    .map_or(word.len(), |(i, _)| i);
+                (word, 0..end)
+            })
+            .filter(|(word, _)| !targets.contains(&word.as_str()))
+            .collect::<Vec<(String, Range<usize>)>>();
+        words.sort_by(|(a, _), (b, _)| self.sort_order.compare(a, b));
+        let mut expansions = targets
+            .into_iter()
+            .map(|target| (target.to_string(), 0..0))
+            .collect::<Vec<(String, Range<usize>)>>();
+        // Targets of several aliases are only returned once
+        let mut seen = BTreeSet::new();
+        expansions.retain(|(target, _)| seen.insert(target.clone()));
+        expansions.extend(words);
+        Some(expansions)
+    }
+
+    /// Returns an optional vector of completed words for the word under the cursor, together
+    /// with the byte range of `line` the completion replaces. The part of the word before the
+    /// cursor is completed like [CompletionTree::complete_words] completes the last word, and
+    /// the range covers the whole word, including any part after the cursor. Returns None if
+    /// nothing matches or `cursor` isn't a character boundary within `line`.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line being edited
+    /// * `cursor`  The byte offset of the cursor in `line`
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile");
+    /// assert_eq!(
+    ///     completions.complete_at("to the batxx mobile", 9),
+    ///     Some((vec!["batman".to_string(), "batmobile".to_string()], 7..12)));
+    /// ```
+    pub fn complete_at(&self, line: &str, cursor: usize) -> Option<(Vec<String>, Range<usize>)> {
+        if !line.is_char_boundary(cursor) {
+            return None;
+        }
+        let (head, tail) = line.split_at(cursor);
+        let last_word = self.completable_word(head)?;
+        if !head.ends_with(last_word) {
+            return None;
+        }
+        let words = self.complete_words(head)?;
+        let start = cursor - last_word.len();
+        Some((words, start..cursor + self.word_end(tail)))
+    }
+
+    /// Returns the byte range and completions of every word in `line` that has completions.
+    /// Each word is completed on its own, like [CompletionTree::complete_words] completes the
+    /// last word, so several ambiguous words each get their full list of candidates. Empty
+    /// words, words shorter than the minimum completion length and words without completions
+    /// are left out. The result is ordered by position in `line`.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile");
+    /// assert_eq!(
+    ///     completions.complete_all_tokens("bat and rob"),
+    ///     vec![
+    ///         (0..3, vec!["batman".to_string(), "batmobile".to_string()]),
+    ///         (8..11, vec!["robin".to_string()]),
+    ///     ]);
+    /// ```
+    pub fn complete_all_tokens(&self, line: &str) -> Vec<(Range<usize>, Vec<String>)> {
+        self.split(line)
+            .into_iter()
+            .filter(|token| !token.is_empty())
+            .filter_map(|token| {
+                let start = token.as_ptr() as usize - line.as_ptr() as usize;
+                let words = self.complete_words(token)?;
+                Some((start..start + token.len(), words))
+            })
+            .collect()
+    }
+
+    /// Returns an optional vector of completions that tolerate typos in the provided input.
+    /// A word matches if any of its prefixes is within `max_distance` edits (insertions,
+    /// deletions or substitutions) of the last word in `line`. Results are sorted by edit
+    /// distance and then alphabetically.
+    ///
+    /// The edit distance is calculated incrementally while walking the tree so branches that
+    /// can't match are never visited.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`            The line to complete
+    ///   In case of multiple words, only the last will be completed
+    /// * `max_distance`    The maximum amount of edits allowed
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile");
+    /// assert_eq!(
+    ///     completions.complete_fuzzy("to the btman", 1),
+    ///     Some(vec!["to the batman".to_string()]));
+    /// assert_eq!(
+    ///     completions.complete_fuzzy("btm", 1),
+    ///     Some(vec!["batman", "batmobile"].iter().map(|s| s.to_string()).collect()));
+    /// ```
+    pub fn complete_fuzzy(&self, line: &str, max_distance: usize) -> Option<Vec<String>> {
+        let last_word = self.completable_word(line)?;
+        let target = self.fold(last_word).chars().collect::<Vec<char>>();
+        let row = (0..=target.len()).collect::<Vec<usize>>();
+        let mut matches = vec![];
+        self.root.collect_fuzzy(
+            &target,
+            &row,
+            row[target.len()],
+            max_distance,
+            "".to_string(),
+            &mut matches,
+        );
+        if matches.is_empty() {
+            return None;
+        }
+        let head = line.strip_suffix(last_word).unwrap_or(line);
+        let mut matches = matches
+            .into_iter()
+            .map(|(distance, word, leaf)| {
+                let word = leaf.display().map(String::from).unwrap_or(word);
+                (distance, format!("{}{}", head, word))
+            })
+            .collect::<Vec<(usize, String)>>();
+        matches.sort();
+        Some(
+            matches
+                .into_iter()
+                .map(|(_, completion)| completion)
+                .collect(),
+        )
+    }
+
+    /// Returns the words within `max_distance` edits (insertions, deletions or substitutions)
+    /// of `word`, sorted by edit distance and then alphabetically. Unlike
+    /// [CompletionTree::complete_fuzzy] the whole word is compared rather than a prefix,
+    /// which suits "did you mean" corrections of a word that has been typed in full.
+    ///
+    /// Like [CompletionTree::complete_fuzzy] the edit distance is calculated while walking the
+    /// tree, and branches where every prefix is already too far off are never visited.
+    ///
+    /// # Arguments
+    ///
+    /// * `word`            The possibly misspelled word
+    /// * `max_distance`    The maximum amount of edits allowed
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile");
+    /// assert_eq!(completions.suggest_corrections("batmn", 1), vec!["batman".to_string()]);
+    /// assert!(completions.suggest_corrections("bat", 1).is_empty());
+    /// ```
+    pub fn suggest_corrections(&self, word: &str, max_distance: usize) -> Vec<String> {
+        let target = self.fold(word).chars().collect::<Vec<char>>();
+        let row = (0..=target.len()).collect::<Vec<usize>>();
+        let mut matches = vec![];
+        self.root
+            .collect_corrections(&target, &row, max_distance, "".to_string(), &mut matches);
+        let mut matches = matches
+            .into_iter()
+            .map(|(distance, word, leaf)| {
+                (distance, leaf.display().map(String::from).unwrap_or(word))
+            })
+            .collect::<Vec<(usize, String)>>();
+        matches.sort();
+        let mut corrections = matches.into_iter().map(|(_, word)| word).collect();
+        dedup_completions(&mut corrections);
+        corrections
+    }
+
+    /// Returns the line extended with the longest unambiguous continuation of its last word.
+    /// The tree is walked from the last word until a word ends or the path branches.
+    /// Returns None if nothing matches the last word.
+    ///
+    /// The continuation is taken from the stored form of the words and appended to the line
+    /// as typed. In case insensitive trees it's lowercase and the display form isn't used, so
+    /// "BA" is extended to "BAtman" rather than "Batman".
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to extend
+    ///   In case of multiple words, only the last will be extended
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile");
+    /// assert_eq!(
+    ///     completions.longest_common_prefix("to the ba"),
+    ///     Some("to the batm".to_string()));
+    /// assert_eq!(
+    ///     completions.longest_common_prefix("ro"),
+    ///     Some("robin".to_string()));
+    /// assert_eq!(completions.longest_common_prefix("jo"), None);
+    /// ```
+    pub fn longest_common_prefix(&self, line: &str) -> Option<String> {
+        let (_, mut node) = self.find_last_word(line)?;
+        let mut extended = line.to_string();
+        while !node.is_leaf() && node.subnodes.len() == 1 {
+            let (c, subnode) = node.subnodes.iter().next()?;
+            extended.push(*c);
+            node = subnode;
+        }
+        #[cfg(feature = "unicode-segmentation")]
+        if self.graphemes && extended.len() > line.len() {
+            // Back off to the start of the last cluster if the tree continues it
+            let (start, last) = extended.grapheme_indices(true).next_back()?;
+            let continued = node.subnodes.keys().any(|c| {
+                let mut cluster = last.to_string();
+                cluster.push(*c);
+                cluster.graphemes(true).count() == 1
+            });
+            if continued {
+                extended.truncate(start.max(line.len()));
+            }
+        }
+        Some(extended)
+    }
+
+    /// Returns an optional vector of at most `max` completions based on the provided input.
+    /// Collection stops as soon as `max` completions have been found, so completions are
+    /// returned in the order they are stored in the tree, which is alphabetical by their
+    /// stored form. Unlike [CompletionTree::complete] the result ignores
+    /// [CompletionTree::sort_order], aliases and [CompletionTree::max_results], so it only
+    /// matches the first `max` entries of [CompletionTree::complete] with default settings.
+    /// Returns None if `max` is 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    /// * `max`     The maximum amount of completions to return
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// assert_eq!(
+    ///     completions.complete_limited("bat", 2),
+    ///     Some(vec!["batcave", "batman"].iter().map(|s| s.to_string()).collect()));
+    /// assert_eq!(completions.complete_limited("joker", 2), None);
+    /// ```
+    pub fn complete_limited(&self, line: &str, max: usize) -> Option<Vec<String>> {
+        if max == 0 {
+            return None;
+        }
+        let (last_word, targets, node) = self.find_completions(line)?;
+        let mut completions = expansions(line, last_word, &targets);
+        dedup_completions(&mut completions);
+        completions.truncate(max);
+        // Regular completions equal to an expansion take a slot of their own while collecting
+        let mut leaves = vec![];
+        if let Some(node) = node {
+            node.collect_limited("".to_string(), max, &mut leaves);
+        }
+        for (ext, leaf) in leaves {
+            if completions.len() >= max {
+                break;
+            }
+            let completion = completion(line, last_word, &ext, leaf);
+            if !completions.contains(&completion) {
+                self.mark_used(last_word, Some(ext.as_str()));
+                completions.push(completion);
+            }
+        }
+        Some(completions)
+    }
+
+    /// Returns an optional vector of completions based on the provided input, limited to words
+    /// that extend the last word by at most `max_suffix` characters. Branches deeper than
+    /// that are never visited. A word matching the last word exactly is always included.
+    /// Aliases and [CompletionTree::max_results] apply like they do to
+    /// [CompletionTree::complete].
+    ///
+    /// # Arguments
+    ///
+    /// * `line`        The line to complete
+    ///   In case of multiple words, only the last will be completed
+    /// * `max_suffix`  The maximum amount of characters to add to the last word
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// assert_eq!(
+    ///     completions.complete_max_depth("bat", 4),
+    ///     Some(vec!["batcave", "batman"].iter().map(|s| s.to_string()).collect()));
+    /// ```
+    pub fn complete_max_depth(&self, line: &str, max_suffix: usize) -> Option<Vec<String>> {
+        let (last_word, targets, node) = self.find_completions(line)?;
+        let mut leaves = vec![];
+        if let Some(node) = node {
+            node.collect_depth("".to_string(), max_suffix, &mut leaves);
+        }
+        self.finish_completions(line, last_word, &targets, &leaves, |completions| {
+            completions.sort_by(|a, b| self.sort_order.compare(a, b))
+        })
+    }
+
+    /// Returns an optional vector of completions based on the provided input, ordered by how
+    /// many times each word has been inserted. The most frequently inserted words come first
+    /// and words with the same count are ordered alphabetically. Aliases and
+    /// [CompletionTree::max_results] apply like they do to [CompletionTree::complete].
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// completions.insert("batmobile");
+    /// assert_eq!(
+    ///     completions.complete_ranked("bat"),
+    ///     Some(vec!["batmobile", "batcave", "batman"].iter().map(|s| s.to_string()).collect()));
+    /// ```
+    pub fn complete_ranked(&self, line: &str) -> Option<Vec<String>> {
+        let (last_word, targets, node) = self.find_completions(line)?;
+        let mut leaves = vec![];
+        if let Some(node) = node {
+            node.collect_leaves("".to_string(), &mut leaves);
+        }
+        leaves.sort_by_key(|(_, leaf)| Reverse(leaf.count()));
+        self.finish_completions(line, last_word, &targets, &leaves, |_| {})
+    }
+
+    /// Returns an optional vector of completions based on the provided input, ordered by when
+    /// each word was first inserted. The earliest inserted words come first. Unlike
+    /// [CompletionTree::complete_ranked] inserting a word again doesn't change its position.
+    ///
+    /// The order is only known while [CompletionTree::set_track_insertion_order] is enabled.
+    /// Otherwise the completions are returned in alphabetical order. Aliases and
+    /// [CompletionTree::max_results] apply like they do to [CompletionTree::complete].
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_track_insertion_order(true);
+    /// completions.insert("batmobile batman batcave batmobile");
+    /// assert_eq!(
+    ///     completions.complete_by_insertion_order("bat"),
+    ///     Some(vec!["batmobile", "batman", "batcave"].iter().map(|s| s.to_string()).collect()));
+    /// ```
+    pub fn complete_by_insertion_order(&self, line: &str) -> Option<Vec<String>> {
+        let (last_word, targets, node) = self.find_completions(line)?;
+        let mut leaves = vec![];
+        if let Some(node) = node {
+            node.collect_leaves("".to_string(), &mut leaves);
+        }
+        if let Some(order) = &self.insert_order {
+            let folded = self.fold(last_word);
+            leaves.sort_by_cached_key(|(ext, _)| order.get(&format!("{}{}", folded, ext)).copied());
+        }
+        self.finish_completions(line, last_word, &targets, &leaves, |_| {})
+    }
+
+    /// Returns an optional vector of the `k` most frequently inserted completions based on
+    /// the provided input. The result is ordered like [CompletionTree::complete_ranked] but
+    /// only the `k` best completions are kept while walking the tree, so the full set of
+    /// completions is never collected or sorted.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix`  The line to complete
+    ///   In case of multiple words, only the last will be completed
+    /// * `k`       The maximum amount of completions to return
+    ///   Returns None if `k` is 0
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// completions.insert("batmobile batcave batmobile");
+    /// assert_eq!(
+    ///     completions.top_k("bat", 2),
+    ///     Some(vec!["batmobile", "batcave"].iter().map(|s| s.to_string()).collect()));
+    /// assert_eq!(completions.top_k("bat", 0), None);
+    /// ```
+    pub fn top_k(&self, prefix: &str, k: usize) -> Option<Vec<String>> {
+        if k == 0 {
+            return None;
+        }
+        let (last_word, node) = self.find_last_word(prefix)?;
+        let mut heap = BinaryHeap::new();
+        node.visit_leaves(&mut String::new(), &mut |ext, leaf| {
+            // Words inserted fewer times than the worst kept one can't make it into the heap
+            if heap.len() == k
+                && heap
+                    .peek()
+                    .is_some_and(|Reverse((count, _))| leaf.count() < *count)
+            {
+                return;
+            }
+            let completion = completion(prefix, last_word, ext, leaf);
+            heap.push(Reverse((leaf.count(), Reverse(completion))));
+            if heap.len() > k {
+                heap.pop();
+            }
+        });
+        Some(
+            heap.into_sorted_vec()
+                .into_iter()
+                .map(|Reverse((_, Reverse(completion)))| completion)
+                .collect(),
+        )
+    }
+
+    /// Returns the number of completions [CompletionTree::complete] would return for the
+    /// provided input, including alias expansions. Unless an alias matches, no completions
+    /// are built. Returns 0 if nothing matches.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix`  The line to complete
+    ///   In case of multiple words, only the last will be completed
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// assert_eq!(completions.count_completions("bat"), 3);
+    /// assert_eq!(completions.count_completions("joker"), 0);
+    /// ```
+    pub fn count_completions(&self, prefix: &str) -> usize {
+        let last_word = match self.completable_word(prefix) {
+            Some(last_word) => last_word,
+            None => return 0,
+        };
+        let node = self.root.find(self.fold(last_word).chars());
+        let expansions = expansions(prefix, last_word, &self.alias_targets(last_word));
+        if expansions.is_empty() {
+            return node.map_or(0, |node| node.word_count() as usize);
+        }
+        // Expansions that are also regular completions are only returned once
+        let mut leaves = vec![];
+        if let Some(node) = node {
+            node.collect_leaves("".to_string(), &mut leaves);
+        }
+        let mut completions = expansions
+            .into_iter()
+            .chain(
+                leaves
+                    .iter()
+                    .map(|(ext, leaf)| completion(prefix, last_word, ext, leaf)),
+            )
+            .collect();
+        dedup_completions(&mut completions);
+        completions.len()
+    }
+
+    /// Returns the number of words in the tree starting with `prefix`, including the prefix
+    /// itself if it's a word. Unlike [CompletionTree::count_completions] the prefix isn't
+    /// split into words or subject to the minimum completion length, so an empty prefix
+    /// counts every word in the tree. The prefix is matched like [CompletionTree::subtree]
+    /// matches it.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix`  The prefix of the words to count
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// assert_eq!(completions.word_count_with_prefix("batm"), 2);
+    /// assert_eq!(completions.word_count_with_prefix(""), 5);
+    /// assert_eq!(completions.word_count_with_prefix("joker"), 0);
+    /// ```
+    pub fn word_count_with_prefix(&self, prefix: &str) -> u32 {
+        self.subtree(prefix).map_or(0, |node| node.word_count())
+    }
+
+    /// Returns true if [CompletionTree::complete] would return any completions for the
+    /// provided input, counting alias expansions and [CompletionTree::max_results]. The search
+    /// stops at the first word found, or one past the cap, and no completions are built, which
+    /// makes this cheap enough to call on every keystroke.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin");
+    /// assert!(completions.has_completions("to the bat"));
+    /// assert!(!completions.has_completions("joker"));
+    /// ```
+    pub fn has_completions(&self, line: &str) -> bool {
+        let (_, targets, node) = match self.find_completions(line) {
+            Some(found) => found,
+            None => return false,
+        };
+        match self.max_results {
+            Some(cap) => {
+                let found = Leaves::new(node).take(cap.saturating_add(1)).count();
+                found <= cap && (found > 0 || !targets.is_empty())
+            }
+            None => !targets.is_empty() || node.is_some_and(|node| node.has_leaf()),
+        }
+    }
+
+    /// Returns true if `prefix` leads to a node in the tree, telling input that could still
+    /// become a word apart from a dead end. Only the path is walked, the prefix is matched like
+    /// [CompletionTree::subtree] matches it. Unlike [CompletionTree::has_completions] the prefix
+    /// isn't split into words or subject to the minimum completion length, and no words are
+    /// searched for below the node.
+    ///
+    /// Nodes that are no longer part of any word are pruned on removal, so a path that exists
+    /// always leads to a word. The exception is the empty prefix, which reaches the root of
+    /// an empty tree too.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix`  The prefix to look up
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin");
+    /// assert!(completions.prefix_node_exists("batm"));
+    /// assert!(!completions.prefix_node_exists("batx"));
+    /// ```
+    pub fn prefix_node_exists(&self, prefix: &str) -> bool {
+        self.subtree(prefix).is_some()
+    }
+
+    /// Returns the completions [CompletionTree::complete] would return for the provided
+    /// input, unless there are more than `max` of them. The matches are counted before any
+    /// completions are built, so rejecting a short prefix matching the whole tree is cheap.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`    The line to complete
+    ///   In case of multiple words, only the last will be completed
+    /// * `max`     The maximum amount of completions to return
+    ///
+    /// # Errors
+    ///
+    /// Returns [CompleteError::NoMatch] if nothing matches and [CompleteError::TooMany] with
+    /// the total amount of matches if there are more than `max`, or more than the cap set
+    /// using [CompletionTree::set_max_results].
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::{CompleteError, CompletionTree};
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// assert_eq!(
+    ///     completions.try_complete("batm", 2),
+    ///     Ok(vec!["batman".to_string(), "batmobile".to_string()]));
+    /// assert_eq!(completions.try_complete("bat", 2), Err(CompleteError::TooMany(3)));
+    /// assert_eq!(completions.try_complete("joker", 2), Err(CompleteError::NoMatch));
+    /// ```
+    pub fn try_complete(&self, line: &str, max: usize) -> Result<Vec<String>, CompleteError> {
+        let max = self.max_results.map_or(max, |cap| cap.min(max));
+        match self.count_completions(line) {
+            0 => Err(CompleteError::NoMatch),
+            total if total > max => Err(CompleteError::TooMany(total)),
+            _ => self.complete(line).ok_or(CompleteError::NoMatch),
+        }
+    }
+
+    /// Finds what the last word in `line` completes to: the targets of the aliases starting
+    /// with it and the node matching it. Returns None if the line is empty or neither an alias
+    /// nor a word matches.
+    fn find_completions<'a>(
+        &self,
+        line: &'a str,
+    ) -> Option<(&'a str, Vec<&str>, Option<&CompletionNode>)> {
+        let last_word = self.completable_word(line)?;
+        let targets = self.alias_targets(last_word);
+        let node = self.root.find(self.fold(last_word).chars());
+        if targets.is_empty() && node.is_none() {
+            return None;
+        }
+        Some((last_word, targets, node))
+    }
+
+    /// Marks the words completing `last_word` by `exts` as used, if the tree has a capacity
+    fn mark_used<'a, I: IntoIterator<Item = &'a str>>(&self, last_word: &str, exts: I) {
+        if let Some(recency) = &self.recency {
+            let stem = self.fold(last_word);
+            for ext in exts {
+                recency.mark_used(&format!("{}{}", stem, ext));
+            }
+        }
+    }
+
+    /// Finds the node matching the last word in `line`. Returns the last word together with
+    /// the node or None if the line is empty or nothing matches.
+    fn find_last_word<'a>(&self, line: &'a str) -> Option<(&'a str, &CompletionNode)> {
+        let last_word = self.completable_word(line)?;
+        let node = self.root.find(self.fold(last_word).chars())?;
+        Some((last_word, node))
+    }
+
+    /// Returns the last word in `line` if it is long enough to be completed
+    fn completable_word<'a>(&self, line: &'a str) -> Option<&'a str> {
+        if self.complete_empty && line.trim().is_empty() {
+            return Some(&line[line.len()..]);
+        }
+        if line.is_empty() {
+            return None;
+        }
+        let last_word = self.last_word(line);
+        if self.complete_empty && last_word.is_empty() {
+            return Some(last_word);
+        }
+        if self.char_len(last_word) < self.min_completion_len {
+            return None;
+        }
+        Some(last_word)
+    }
+
+    /// Returns the byte length of the word at the start of `text`, up to the first separator
+    fn word_end(&self, text: &str) -> usize {
+        let end = match &self.separator {
+            WordSeparator::Whitespace => text.find(char::is_whitespace),
+            WordSeparator::Separator(sep) => text.find(sep),
+            WordSeparator::AnyOf(chars) => text.find(&chars[..]),
+            WordSeparator::Owned(sep) => text.find(sep.as_str()),
+            #[cfg(feature = "regex")]
+            WordSeparator::Regex(regex) => regex.find(text).map(|m| m.start()),
+        };
+        end.unwrap_or(text.len())
+    }
+
+    /// Returns the last word in `line` using the configured [WordSeparator]. A line ending in
+    /// a separator starts a new, empty, word.
+    fn last_word<'a>(&self, line: &'a str) -> &'a str {
+        match &self.separator {
+            WordSeparator::Whitespace if line.ends_with(char::is_whitespace) => &line[line.len()..],
+            WordSeparator::Whitespace => line.split_whitespace().last().unwrap_or(""),
+            WordSeparator::Separator(sep) => line.rsplit(sep).next().unwrap_or(""),
+            WordSeparator::AnyOf(chars) => line.rsplit(&chars[..]).next().unwrap_or(""),
+            WordSeparator::Owned(sep) => line.rsplit(sep.as_str()).next().unwrap_or(""),
+            #[cfg(feature = "regex")]
+            WordSeparator::Regex(regex) => regex.split(line).last().unwrap_or(""),
+        }
+    }
+
+    /// Returns the length of `word` in characters, or in grapheme clusters in grapheme mode
+    fn char_len(&self, word: &str) -> usize {
+        #[cfg(feature = "unicode-segmentation")]
+        if self.graphemes {
+            return word.graphemes(true).count();
+        }
+        word.chars().count()
+    }
+
+    /// Returns the word as it should be stored in and looked up from the tree
+    fn fold<'a>(&self, word: &'a str) -> Cow<'a, str> {
+        let word = self.normalized(word);
+        #[cfg(feature = "unicode-normalization")]
+        let word = if self.fold_diacritics {
+            Cow::Owned(
+                word.nfd()
+                    .filter(|c| !unicode_normalization::char::is_combining_mark(*c))
+                    .collect(),
+            )
+        } else {
+            word
+        };
+        if self.case_insensitive {
+            Cow::Owned(word.to_lowercase())
+        } else {
+            word
+        }
+    }
+
+    /// Returns the word in NFC form if normalization is enabled
+    fn normalized<'a>(&self, word: &'a str) -> Cow<'a, str> {
+        #[cfg(feature = "unicode-normalization")]
+        if self.normalize {
+            return Cow::Owned(word.nfc().collect());
+        }
+        Cow::Borrowed(word)
+    }
+
+    /// Clears all the data from the tree. This also resets the sequence used by
+    /// [CompletionTree::complete_by_insertion_order].
+    ///
+    /// The configuration of the tree, like inclusions, exclusions, the [WordSeparator] and
+    /// word length limits, is kept. A built suffix index stays enabled but is emptied. Use
+    /// [CompletionTree::reset] to restore the default configuration as well.
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// assert_eq!(completions.word_count(), 5);
+    /// assert_eq!(completions.size(), 24);
+    /// completions.clear();
+    /// assert_eq!(completions.size(), 1);
+    /// assert_eq!(completions.word_count(), 0);
+    /// ```
+    pub fn clear(&mut self) {
+        self.root.clear();
+        self.insert_seq = 0;
+        if let Some(order) = &mut self.insert_order {
+            order.clear();
+        }
+        self.word_count = 0;
+        if let Some(suffixes) = &mut self.suffixes {
+            suffixes.clear();
+        }
+        if let Some(substrings) = &mut self.substrings {
+            substrings.clear();
+        }
+        if let Some(infixes) = &mut self.infixes {
+            infixes.clear();
+        }
+        if let Some(recency) = &mut self.recency {
+            recency.clear();
+        }
+    }
+
+    /// Restores the tree to the state of [CompletionTree::default], removing all words and
+    /// resetting every setting. Unlike [CompletionTree::clear] nothing of the configuration is
+    /// kept.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::{CompletionTree, WordSeparator};
+    ///
+    /// let mut completions = CompletionTree::with_inclusions(&['/']);
+    /// completions.separator(WordSeparator::Separator("|"));
+    /// completions.insert("/batman|/robin");
+    /// completions.reset();
+    /// assert_eq!(completions.word_count(), 0);
+    /// completions.insert("batman|robin");
+    /// assert_eq!(completions.words(), vec!["batman".to_string()]);
+    /// ```
+    pub fn reset(&mut self) {
+        *self = CompletionTree::default();
+    }
+
+    /// Returns an immutable copy of the tree that can be shared between threads and completed
+    /// on without locking, while this tree keeps being modified. Cloning the returned `Arc`
+    /// is cheap, but taking the snapshot copies the whole tree, so it costs time and memory
+    /// proportional to [CompletionTree::size]. A snapshot doesn't see words inserted or
+    /// removed after it was taken.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman");
+    /// let snapshot = completions.snapshot();
+    /// completions.insert("batmobile");
+    /// assert_eq!(snapshot.complete("bat"), Some(vec!["batman".to_string()]));
+    /// assert_eq!(completions.complete("bat").unwrap().len(), 2);
+    /// ```
+    pub fn snapshot(&self) -> Arc<CompletionTree> {
+        Arc::new(self.clone())
+    }
+
+    /// Reduces the memory used by the tree after bulk inserts or removals. The children of
+    /// every node are stored in a `BTreeMap`, which doesn't over-allocate like a `Vec` or
+    /// `HashMap` but can be left with partially filled internal nodes. Each map is rebuilt
+    /// from its sorted entries, which packs it as densely as possible, and stored display
+    /// forms are shrunk to their length. With the `fast-insert` feature the children are
+    /// stored in a `HashMap` instead, which is rebuilt at the capacity needed for its
+    /// entries. The words and configuration of the tree are left untouched.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// completions.shrink_to_fit();
+    /// assert_eq!(completions.word_count(), 5);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        self.root.shrink_to_fit();
+        if let Some(suffixes) = &mut self.suffixes {
+            suffixes.shrink_to_fit();
+        }
+    }
+
+    /// Returns an estimate of the memory used by the tree in bytes, including the tree itself.
+    /// Every node is counted with its character key and the approximate bookkeeping of the map
+    /// holding it, together with any stored display form. The shared character sets are
+    /// counted once. The actual footprint depends on the allocator and the internal layout of
+    /// `BTreeMap` so the value should only be used to compare trees or track growth.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// let empty = completions.memory_bytes();
+    /// completions.insert("batman robin batmobile");
+    /// assert!(completions.memory_bytes() > empty);
+    /// ```
+    pub fn memory_bytes(&self) -> usize {
+        let charset = |set: &BTreeSet<char>| {
+            2 * size_of::<usize>() + size_of::<BTreeSet<char>>() + set.len() * size_of::<char>()
+        };
+        size_of::<CompletionTree>()
+            + charset(&self.inclusions)
+            + charset(&self.exclusions)
+            + self.root.heap_bytes()
+            + self
+                .suffixes
+                .as_ref()
+                .map_or(0, |suffixes| suffixes.heap_bytes())
+    }
+
+    /// Returns the node reached by walking `prefix` from the root of the tree, allowing the
+    /// words below it to be traversed using [CompletionNode::children]. The prefix is matched
+    /// like the words of the tree, so it's lowercased in case insensitive trees. Returns None
+    /// if no word starts with the prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix`  The prefix to look up
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman batmobile batcave");
+    /// let node = completions.subtree("batm").unwrap();
+    /// assert_eq!(node.children().map(|(c, _)| c).collect::<String>(), "ao");
+    /// assert!(!node.is_leaf());
+    /// assert!(completions.subtree("batman").unwrap().is_leaf());
+    /// ```
+    pub fn subtree(&self, prefix: &str) -> Option<&CompletionNode> {
+        self.root.find(self.fold(prefix).chars())
+    }
+
+    /// Returns the sorted characters that can follow `prefix` in the words of the tree. The
+    /// prefix is matched like [CompletionTree::subtree] matches it. Returns None if no word
+    /// starts with the prefix and an empty vector if the prefix is a word nothing extends.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix`  The prefix to look up
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman batmobile batcave");
+    /// assert_eq!(completions.next_chars("bat"), Some(vec!['c', 'm']));
+    /// assert_eq!(completions.next_chars("batman"), Some(vec![]));
+    /// assert_eq!(completions.next_chars("robin"), None);
+    /// ```
+    pub fn next_chars(&self, prefix: &str) -> Option<Vec<char>> {
+        let node = self.subtree(prefix)?;
+        Some(node.sorted_subnodes().map(|(c, _)| *c).collect())
+    }
+
+    /// Returns a count of how many words that exist in the tree. The count is kept up to date
+    /// as words are inserted and removed, so this doesn't walk the tree.
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// assert_eq!(completions.word_count(), 5);
     /// ```
     pub fn word_count(&self) -> u32 {
-        self.root.word_count()
+        self.word_count
+    }
+
+    /// Returns true if the tree holds no words
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// assert!(completions.is_empty());
+    /// completions.insert("batman");
+    /// assert!(!completions.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.word_count == 0
+    }
+
+    /// Returns the size of the tree, the amount of nodes, not words
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// assert_eq!(completions.size(), 24);
+    /// ```
+    pub fn size(&self) -> u32 {
+        self.root.subnode_count()
+    }
+
+    /// Returns the amount of nodes at each depth of the tree, where index `i` holds the number
+    /// of nodes `i` characters below the root. Index 0 is the root itself, so the histogram is
+    /// never empty and its values sum up to [CompletionTree::size].
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_min_word_len(2);
+    /// completions.insert("ab ac bcd");
+    /// assert_eq!(completions.depth_histogram(), vec![1, 2, 3, 1]);
+    /// ```
+    pub fn depth_histogram(&self) -> Vec<u32> {
+        let mut histogram = vec![];
+        self.root.count_depths(0, &mut histogram);
+        histogram
+    }
+
+    /// Returns a Graphviz DOT `digraph` of the tree, useful for debugging unexpected
+    /// completions. Each node is labeled with the word it represents, leaves are drawn as
+    /// double circles and edges are labeled with their character. Children are ordered
+    /// alphabetically.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_min_word_len(2);
+    /// completions.insert("ab");
+    /// assert_eq!(
+    ///     completions.to_dot(),
+    ///     concat!(
+    ///         "digraph completions {\n",
+    ///         "    n0 [label=\"\" shape=circle];\n",
+    ///         "    n0 -> n1 [label=\"a\"];\n",
+    ///         "    n1 [label=\"a\" shape=circle];\n",
+    ///         "    n1 -> n2 [label=\"b\"];\n",
+    ///         "    n2 [label=\"ab\" shape=doublecircle];\n",
+    ///         "}\n"));
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph completions {\n");
+        let mut next_id = 0;
+        self.root
+            .write_dot(&mut String::new(), &mut next_id, &mut out);
+        out.push_str("}\n");
+        out
+    }
+
+    /// Returns all the words in the tree sorted alphabetically
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile");
+    /// assert_eq!(
+    ///     completions.words(),
+    ///     vec!["batman", "batmobile", "robin"].iter().map(|s| s.to_string()).collect::<Vec<String>>());
+    /// ```
+    pub fn words(&self) -> Vec<String> {
+        let mut leaves = vec![];
+        self.root.collect_leaves("".to_string(), &mut leaves);
+        let mut words = leaves
+            .into_iter()
+            .map(|(word, leaf)| leaf.display().map(String::from).unwrap_or(word))
+            .collect::<Vec<String>>();
+        words.sort();
+        words
+    }
+
+    /// Returns an iterator over every word in the tree together with how many times it has
+    /// been inserted. Insertion counts are always tracked, they are the same counts used by
+    /// [CompletionTree::complete_ranked] and [CompletionTree::prune_below]. Words are yielded
+    /// in the order they are stored in the tree, which is alphabetical by their stored form.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batman");
+    /// assert_eq!(
+    ///     completions.iter_counts().collect::<Vec<(String, u32)>>(),
+    ///     vec![("batman".to_string(), 2), ("robin".to_string(), 1)]);
+    /// ```
+    pub fn iter_counts(&self) -> impl Iterator<Item = (String, u32)> + '_ {
+        let mut leaves = vec![];
+        self.root.collect_leaves("".to_string(), &mut leaves);
+        leaves.into_iter().map(|(word, leaf)| {
+            (
+                leaf.display().map(String::from).unwrap_or(word),
+                leaf.count(),
+            )
+        })
+    }
+
+    /// Returns the minimum word length to complete. This allows you
+    /// to pass full sentences to `insert()` and not worry about
+    /// pruning out small words like "a" or "to", because they will be
+    /// ignored.
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_min_word_len(4);
+    /// completions.insert("one two three four five");
+    /// assert_eq!(completions.word_count(), 3);
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_min_word_len(1);
+    /// completions.insert("one two three four five");
+    /// assert_eq!(completions.word_count(), 5);
+    /// ```
+    pub fn min_word_len(&self) -> usize {
+        self.min_word_len
+    }
+
+    /// Sets the minimum word length to complete on. Smaller words are
+    /// ignored. This only affects future calls to `insert()` -
+    /// changing this won't start completing on smaller words that
+    /// were added in the past, nor will it exclude larger words
+    /// already inserted into the completion tree.
+    pub fn set_min_word_len(&mut self, len: usize) {
+        self.min_word_len = len;
+    }
+
+    /// Returns the maximum word length to complete, if any. Defaults to None which means
+    /// that there is no upper limit.
+    pub fn max_word_len(&self) -> Option<usize> {
+        self.max_word_len
+    }
+
+    /// Sets the maximum word length to complete on. Longer words are
+    /// ignored. Like [CompletionTree::set_min_word_len] this only affects
+    /// future calls to `insert()`.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_max_word_len(6);
+    /// completions.insert("batman batmobile batcave");
+    /// assert_eq!(completions.max_word_len(), Some(6));
+    /// assert_eq!(completions.word_count(), 1);
+    /// ```
+    pub fn set_max_word_len(&mut self, len: usize) {
+        self.max_word_len = Some(len);
+    }
+
+    /// Returns the minimum length the last word of a line needs to have before completion is
+    /// attempted. Defaults to 1.
+    pub fn min_completion_len(&self) -> usize {
+        self.min_completion_len
+    }
+
+    /// Sets the minimum length, in characters, the last word of a line needs to have before
+    /// completion is attempted. Shorter words will not be completed.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_min_completion_len(3);
+    /// completions.insert("batman robin");
+    /// assert_eq!(completions.complete("ba"), None);
+    /// assert_eq!(
+    ///     completions.complete("bat"),
+    ///     Some(vec!["batman".to_string()]));
+    /// ```
+    pub fn set_min_completion_len(&mut self, len: usize) {
+        self.min_completion_len = len;
+    }
+
+    /// Returns the maximum amount of completions [CompletionTree::complete] returns, if any
+    pub fn max_results(&self) -> Option<usize> {
+        self.max_results
+    }
+
+    /// Sets the maximum amount of completions [CompletionTree::complete] and methods ordering
+    /// its completions differently, like [CompletionTree::complete_ranked], return. Input
+    /// matching more words than `cap` returns None. [CompletionTree::complete] stops walking
+    /// the tree as soon as the cap is exceeded so no more than `cap + 1` words are visited.
+    /// Use [CompletionTree::try_complete] to tell such input apart from input without
+    /// matches. Defaults to None, which doesn't limit completions.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile batcave robber");
+    /// completions.set_max_results(Some(2));
+    /// assert_eq!(completions.complete("bat"), None);
+    /// assert_eq!(
+    ///     completions.complete("rob"),
+    ///     Some(vec!["robber".to_string(), "robin".to_string()]));
+    /// ```
+    pub fn set_max_results(&mut self, cap: Option<usize>) {
+        self.max_results = cap;
+    }
+
+    /// Returns the order [CompletionTree::complete] returns completions in
+    pub fn sort_order(&self) -> SortOrder {
+        self.sort_order
+    }
+
+    /// Sets the order [CompletionTree::complete] returns completions in. Defaults to
+    /// [SortOrder::Alphabetical]. Use [CompletionTree::complete_by] for any other order.
+    ///
+    /// The order applies to every method returning a list of completions, except those that
+    /// rank their results: [CompletionTree::complete_fuzzy] and
+    /// [CompletionTree::suggest_corrections] sort by edit distance,
+    /// [CompletionTree::complete_ranked] and [CompletionTree::top_k] by count and
+    /// [CompletionTree::complete_by_insertion_order] by insertion order. Word listings and
+    /// searches such as [CompletionTree::words] and [CompletionTree::search_substring] stay
+    /// alphabetical.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::{CompletionTree, SortOrder};
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batmobile batman batcave");
+    /// completions.set_sort_order(SortOrder::LongestFirst);
+    /// assert_eq!(
+    ///     completions.complete("bat"),
+    ///     Some(vec!["batmobile", "batcave", "batman"].iter().map(|s| s.to_string()).collect()));
+    /// ```
+    pub fn set_sort_order(&mut self, order: SortOrder) {
+        self.sort_order = order;
+    }
+
+    /// Returns true if the order words are first inserted in is tracked
+    pub fn tracks_insertion_order(&self) -> bool {
+        self.insert_order.is_some()
+    }
+
+    /// Enables or disables tracking the order words are first inserted in, which
+    /// [CompletionTree::complete_by_insertion_order] sorts by. Tracking keeps a copy of every
+    /// word next to the tree, so it's disabled by default. Words already in the tree when
+    /// tracking is enabled are numbered in alphabetical order. Disabling drops the tracked
+    /// order. [CompletionTree::clear] restarts the numbering.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batmobile batman");
+    /// completions.set_track_insertion_order(true);
+    /// completions.insert("batcave");
+    /// assert_eq!(
+    ///     completions.complete_by_insertion_order("bat"),
+    ///     Some(vec!["batman", "batmobile", "batcave"].iter().map(|s| s.to_string()).collect()));
+    /// ```
+    pub fn set_track_insertion_order(&mut self, enabled: bool) {
+        if !enabled {
+            self.insert_order = None;
+            return;
+        }
+        if self.insert_order.is_none() {
+            let mut order = BTreeMap::new();
+            let mut seq = self.insert_seq;
+            self.root.visit_leaves(&mut "".to_string(), &mut |word, _| {
+                order.insert(word.to_string(), seq);
+                seq += 1;
+            });
+            self.insert_seq = seq;
+            self.insert_order = Some(order);
+        }
+    }
+
+    /// Returns the maximum amount of words the tree holds, if any
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// Sets the maximum amount of words the tree holds. Once the tree is full, inserting a
+    /// new word evicts the least recently used word, a word being used when it's inserted or
+    /// returned by [CompletionTree::complete] and similar methods. Defaults to None, which
+    /// doesn't limit the tree.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_capacity(Some(2));
+    /// completions.insert("batman robin");
+    /// completions.complete("bat");
+    /// completions.insert("batmobile");
+    /// assert_eq!(
+    ///     completions.words(),
+    ///     vec!["batman".to_string(), "batmobile".to_string()]);
+    /// ```
+    pub fn set_capacity(&mut self, max_words: Option<usize>) {
+        self.capacity = max_words;
+        if max_words.is_none() {
+            self.recency = None;
+            return;
+        }
+        self.sync_recency();
+        self.enforce_capacity();
+    }
+
+    /// Brings the tracked words of a tree with a capacity in line with the words of the tree.
+    /// Words missing from the tracking are added in the order they were first inserted, if
+    /// known, or alphabetically.
+    fn sync_recency(&mut self) {
+        if self.capacity.is_none() {
+            return;
+        }
+        let mut leaves = vec![];
+        self.root.collect_leaves("".to_string(), &mut leaves);
+        if let Some(order) = &self.insert_order {
+            leaves.sort_by_key(|(word, _)| order.get(word).copied());
+        }
+        let recency = self.recency.get_or_insert_with(Recency::default);
+        let root = &self.root;
+        recency.retain(|word| root.find(word.chars()).is_some_and(|node| node.is_leaf()));
+        for (word, _) in leaves {
+            if !recency.used.contains_key(word.as_str()) {
+                recency.touch(&word);
+            }
+        }
+    }
+
+    /// Evicts the least recently used words until the tree is within its capacity
+    fn enforce_capacity(&mut self) {
+        let capacity = match self.capacity {
+            Some(capacity) => capacity,
+            None => return,
+        };
+        while self.word_count as usize > capacity {
+            match self.recency.as_mut().and_then(Recency::least_recent) {
+                Some(word) => {
+                    // A tracked word that isn't in the tree would be picked again and again
+                    if !self.remove(&word) {
+                        if let Some(recency) = &mut self.recency {
+                            recency.remove(&word);
+                        }
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Returns true if the tree matches words case insensitively
+    pub fn case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    /// Enables or disables case insensitive matching. When enabled, words are lowercased
+    /// before being stored in the tree and completions are looked up using the lowercased
+    /// input. Words differing only by case are stored once and completions are returned using
+    /// the case the word had when it was most recently inserted.
+    ///
+    /// Inclusions are matched against the characters as they are inserted, before lowercasing.
+    /// Non alphabetic inclusions such as `/` or `_` are not affected by case folding.
+    ///
+    /// Switching the mode on a tree holding words stores every word again under its new path,
+    /// keeping its count and the case it was completed in. Words that end up at the same path,
+    /// like "Batman" and "batman", are merged: their counts are added up and the word inserted
+    /// last, or the alphabetically last one if the insertion order isn't tracked, gives the
+    /// completed form. Aliases, indexes and tracked usage are carried over. This takes time
+    /// proportional to [CompletionTree::size], so it's best to pick the mode before inserting
+    /// words.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("Joker");
+    /// completions.set_case_insensitive(true);
+    /// assert_eq!(completions.complete("jo"), Some(vec!["Joker".to_string()]));
+    /// completions.insert("Batman robin");
+    /// assert_eq!(
+    ///     completions.complete("BAT"),
+    ///     Some(vec!["Batman".to_string()]));
+    /// assert_eq!(
+    ///     completions.complete("to the bat"),
+    ///     Some(vec!["to the Batman".to_string()]));
+    /// ```
+    pub fn set_case_insensitive(&mut self, enabled: bool) {
+        if self.case_insensitive != enabled {
+            self.case_insensitive = enabled;
+            self.refold();
+        }
+    }
+
+    /// Stores the words of the tree again under the paths the current normalization and
+    /// folding give them, keeping their counts, display forms, insertion order and usage, and
+    /// refolds the aliases. The indexes that are enabled are rebuilt.
+    fn refold(&mut self) {
+        let mut words = vec![];
+        let order = self.insert_order.as_ref();
+        let recency = self.recency.as_ref();
+        self.root
+            .visit_leaves(&mut "".to_string(), &mut |path, leaf| {
+                let word = leaf.display().unwrap_or(path).to_string();
+                let seq = order.and_then(|order| order.get(path).copied());
+                let used = recency
+                    .and_then(|recency| recency.used.get(path))
+                    .map(|stamp| stamp.used.load(atomic::Ordering::Relaxed));
+                words.push((word, leaf.count(), seq, used));
+            });
+        if words.is_empty() && self.aliases.is_empty() {
+            return;
+        }
+        // Of the words merged into one path the last one inserted sets the display form
+        words.sort_by_key(|(_, _, seq, _)| *seq);
+        let words = words
+            .into_iter()
+            .filter_map(|(word, count, seq, used)| {
+                let (path, display) = self.stored_path(self.truncated(self.normalized(&word)));
+                // An empty path would mark the root as a word
+                if path.is_empty() {
+                    return None;
+                }
+                Some((path.into_owned(), display, count, seq, used))
+            })
+            .collect::<Vec<_>>();
+
+        self.root = CompletionNode::new();
+        self.word_count = 0;
+        for (path, display, count, _, _) in &words {
+            if self
+                .root
+                .insert_counted(path.chars(), display.as_deref(), *count)
+            {
+                self.word_count += 1;
+            }
+        }
+        if let Some(order) = &mut self.insert_order {
+            order.clear();
+            for (path, _, _, seq, _) in &words {
+                if let Some(seq) = seq {
+                    order.entry(path.clone()).or_insert(*seq);
+                }
+            }
+        }
+        if let Some(recency) = &mut self.recency {
+            let mut used = words
+                .iter()
+                .map(|(path, _, _, _, used)| (*used, path))
+                .collect::<Vec<_>>();
+            used.sort();
+            recency.clear();
+            for (_, path) in used {
+                recency.touch(path);
+            }
+        }
+        if self.suffixes.is_some() {
+            self.build_suffix_index();
+        }
+        if self.substrings.is_some() {
+            self.build_substring_index();
+        }
+        if self.infixes.is_some() {
+            self.build_infix_index();
+        }
+        let aliases = core::mem::take(&mut self.aliases);
+        for (alias, targets) in aliases {
+            self.aliases
+                .entry(self.fold(&alias).into_owned())
+                .or_default()
+                .extend(targets);
+        }
+    }
+
+    /// Returns true if words and input are NFC normalized
+    #[cfg(feature = "unicode-normalization")]
+    pub fn normalize(&self) -> bool {
+        self.normalize
+    }
+
+    /// Enables or disables unicode NFC normalization of words and input, so characters with
+    /// multiple representations like "é" and "e" followed by a combining accent match each
+    /// other. Words are stored, and completed, in their normalized form. Words already in the
+    /// tree are normalized right away.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_normalize(true);
+    /// completions.insert("cafe\u{301}s");
+    /// assert_eq!(
+    ///     completions.complete("caf\u{e9}"),
+    ///     Some(vec!["caf\u{e9}s".to_string()]));
+    /// ```
+    #[cfg(feature = "unicode-normalization")]
+    pub fn set_normalize(&mut self, enabled: bool) {
+        if self.normalize != enabled {
+            self.normalize = enabled;
+            self.refold();
+        }
+    }
+
+    /// Returns true if diacritics are ignored when matching words
+    #[cfg(feature = "unicode-normalization")]
+    pub fn fold_diacritics(&self) -> bool {
+        self.fold_diacritics
+    }
+
+    /// Enables or disables diacritic insensitive matching. Words and input are stripped of
+    /// combining marks before they are matched, so "cafe" matches "café", but words are still
+    /// completed in the form they were inserted. Words already in the tree are refolded right
+    /// away.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_min_word_len(3);
+    /// completions.set_fold_diacritics(true);
+    /// completions.insert("caf\u{e9} cafeteria");
+    /// assert_eq!(
+    ///     completions.complete("cafe"),
+    ///     Some(vec!["cafeteria".to_string(), "caf\u{e9}".to_string()]));
+    /// ```
+    #[cfg(feature = "unicode-normalization")]
+    pub fn set_fold_diacritics(&mut self, enabled: bool) {
+        if self.fold_diacritics != enabled {
+            self.fold_diacritics = enabled;
+            self.refold();
+        }
+    }
+
+    /// Returns true if grapheme clusters are kept whole
+    #[cfg(feature = "unicode-segmentation")]
+    pub fn graphemes(&self) -> bool {
+        self.graphemes
+    }
+
+    /// Enables or disables grapheme mode, which treats grapheme clusters, like emoji with
+    /// modifiers or characters followed by combining marks, as atomic. Inserted words are cut
+    /// off before the first cluster containing a character that isn't accepted, and
+    /// [CompletionTree::longest_common_prefix] and [CompletionTree::set_min_completion_len]
+    /// count whole clusters. Words already in the tree are kept as they are.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_min_word_len(1);
+    /// completions.set_graphemes(true);
+    /// completions.insert("cafe\u{301} nai\u{308}ve");
+    /// assert_eq!(completions.words(), vec!["caf".to_string(), "na".to_string()]);
+    /// ```
+    #[cfg(feature = "unicode-segmentation")]
+    pub fn set_graphemes(&mut self, enabled: bool) {
+        self.graphemes = enabled;
+    }
+
+    /// Returns true if ANSI escape sequences are stripped from inserted lines
+    pub fn strip_ansi(&self) -> bool {
+        self.strip_ansi
+    }
+
+    /// Enables or disables stripping ANSI CSI escape sequences, like the color code
+    /// `\x1b[34m`, from lines before they are split into words. Without this a sequence inside
+    /// a word cuts the word short. This only affects future calls to `insert()`.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_strip_ansi(true);
+    /// completions.insert("\x1b[31mdumpster\x1b[0mfire");
+    /// assert_eq!(
+    ///     completions.complete("dum"),
+    ///     Some(vec!["dumpsterfire".to_string()]));
+    /// ```
+    pub fn set_strip_ansi(&mut self, enabled: bool) {
+        self.strip_ansi = enabled;
     }
 
-    /// Returns the size of the tree, the amount of nodes, not words
+    /// Returns true if words made up entirely of ASCII digits are ignored
+    pub fn ignore_numeric(&self) -> bool {
+        self.ignore_numeric
+    }
+
+    /// Enables or disables ignoring words made up entirely of ASCII digits, like "12345".
+    /// Words mixing letters and digits, like "x86", are still inserted. This only affects
+    /// future calls to `insert()`.
+    ///
     /// # Example
     /// ```
     /// extern crate rs_complete;
     /// use rs_complete::CompletionTree;
     ///
     /// let mut completions = CompletionTree::default();
-    /// completions.insert("batman robin batmobile batcave robber");
-    /// assert_eq!(completions.size(), 24);
+    /// completions.set_min_word_len(3);
+    /// completions.set_ignore_numeric(true);
+    /// completions.insert("x86 12345");
+    /// assert_eq!(completions.words(), vec!["x86".to_string()]);
     /// ```
-    pub fn size(&self) -> u32 {
-        self.root.subnode_count()
+    pub fn set_ignore_numeric(&mut self, enabled: bool) {
+        self.ignore_numeric = enabled;
     }
 
-    /// Returns the minimum word length to complete. This allows you
-    /// to pass full sentences to `insert()` and not worry about
-    /// pruning out small words like "a" or "to", because they will be
-    /// ignored.
+    /// Returns true if words are cut off at the first character that isn't accepted
+    pub fn truncate_on_invalid(&self) -> bool {
+        self.truncate_on_invalid
+    }
+
+    /// Sets whether words containing a character that isn't accepted are cut off at that
+    /// character, the default, or rejected as a whole. Rejected words are counted as
+    /// `skipped_other` by [CompletionTree::insert_counting]. This only affects future calls to
+    /// `insert()`.
+    ///
     /// # Example
     /// ```
     /// extern crate rs_complete;
     /// use rs_complete::CompletionTree;
     ///
     /// let mut completions = CompletionTree::default();
-    /// completions.set_min_word_len(4);
-    /// completions.insert("one two three four five");
-    /// assert_eq!(completions.word_count(), 3);
+    /// completions.set_truncate_on_invalid(false);
+    /// completions.insert("batman#robin batmobile");
+    /// assert_eq!(completions.words(), vec!["batmobile".to_string()]);
+    /// ```
+    pub fn set_truncate_on_invalid(&mut self, enabled: bool) {
+        self.truncate_on_invalid = enabled;
+    }
+
+    /// Returns true if only the included characters are accepted
+    pub fn strict_charset(&self) -> bool {
+        self.strict_charset
+    }
+
+    /// Enables or disables the strict character set, which only accepts the characters added
+    /// using [CompletionTree::with_inclusions] and ignores exclusions. This only affects
+    /// future calls to `insert()`, words already in the tree are kept.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::with_inclusions(&['0', '1', 'a', 'b', ':']);
+    /// completions.set_strict_charset(true);
+    /// completions.insert("ab:01:ba abort");
+    /// assert_eq!(
+    ///     completions.words(),
+    ///     vec!["ab".to_string(), "ab:01:ba".to_string()]);
+    /// ```
+    pub fn set_strict_charset(&mut self, enabled: bool) {
+        self.strict_charset = enabled;
+    }
+
+    /// Returns true if an empty line completes to every word in the tree
+    pub fn complete_empty(&self) -> bool {
+        self.complete_empty
+    }
+
+    /// Enables or disables completing empty lines. By default an empty, or whitespace only,
+    /// line has no completions, and neither has a line ending in a separator since it starts
+    /// a new, empty, word. When enabled such lines complete to every word in the tree, with
+    /// the line kept in front of each word like it is for any other input. This ignores
+    /// [CompletionTree::set_min_completion_len].
+    ///
+    /// Every word in the tree is collected into the returned vector, which can be a large
+    /// allocation for big trees.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
     ///
     /// let mut completions = CompletionTree::default();
-    /// completions.set_min_word_len(1);
-    /// completions.insert("one two three four five");
-    /// assert_eq!(completions.word_count(), 5);
+    /// completions.insert("batman robin");
+    /// assert_eq!(completions.complete(""), None);
+    /// completions.set_complete_empty(true);
+    /// assert_eq!(
+    ///     completions.complete(""),
+    ///     Some(vec!["batman".to_string(), "robin".to_string()]));
     /// ```
-    pub fn min_word_len(&self) -> usize {
-        self.min_word_len
+    pub fn set_complete_empty(&mut self, enabled: bool) {
+        self.complete_empty = enabled;
     }
 
-    /// Sets the minimum word length to complete on. Smaller words are
-    /// ignored. This only affects future calls to `insert()` -
-    /// changing this won't start completing on smaller words that
-    /// were added in the past, nor will it exclude larger words
-    /// already inserted into the completion tree.
-    pub fn set_min_word_len(&mut self, len: usize) {
-        self.min_word_len = len;
+    /// Returns true if the sub-words of inserted identifiers are indexed
+    pub fn subword_indexing(&self) -> bool {
+        self.subword_indexing
+    }
+
+    /// Enables or disables sub-word indexing. Words made up of parts separated by camelCase
+    /// humps, `_` or `-` also have each part inserted in lowercase, so "getUserName" can be
+    /// completed from "user". Each part counts as a word of its own. This only affects future
+    /// calls to `insert()`.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.set_min_word_len(3);
+    /// completions.set_subword_indexing(true);
+    /// completions.insert("getUserName");
+    /// assert_eq!(completions.word_count(), 4);
+    /// assert_eq!(
+    ///     completions.complete("user"),
+    ///     Some(vec!["user".to_string()]));
+    /// ```
+    pub fn set_subword_indexing(&mut self, enabled: bool) {
+        self.subword_indexing = enabled;
+    }
+}
+
+/// Splits `word` into its lowercased parts on camelCase humps, `_` and `-`. Returns an empty
+/// vector if the word consists of a single part.
+fn subwords(word: &str) -> Vec<String> {
+    let chars = word.chars().collect::<Vec<char>>();
+    let mut parts = vec![];
+    let mut part = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if *c == '_' || *c == '-' {
+            parts.push(core::mem::take(&mut part));
+            continue;
+        }
+        if is_hump(&chars, i) {
+            parts.push(core::mem::take(&mut part));
+        }
+        part.extend(c.to_lowercase());
+    }
+    parts.push(part);
+    parts.retain(|part| !part.is_empty());
+    if parts.len() > 1 {
+        parts
+    } else {
+        vec![]
+    }
+}
+
+/// Returns true if the character at `i` starts a new camelCase part
+fn is_hump(chars: &[char], i: usize) -> bool {
+    let prev = i.checked_sub(1).map(|i| chars[i]);
+    let next = chars.get(i + 1);
+    chars[i].is_uppercase()
+        && prev.is_some_and(|p| {
+            p.is_lowercase()
+                || p.is_numeric()
+                || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+        })
+}
+
+/// Returns the lowercased rest of `word` from the start of each of its segments, split on
+/// camelCase humps, `_` and `-`
+fn infix_keys(word: &str) -> Vec<String> {
+    let chars = word.chars().collect::<Vec<char>>();
+    let mut keys = vec![];
+    let mut segment_start = true;
+    for (i, (offset, c)) in word.char_indices().enumerate() {
+        if c == '_' || c == '-' {
+            segment_start = true;
+            continue;
+        }
+        if segment_start || is_hump(&chars, i) {
+            keys.push(word[offset..].to_lowercase());
+        }
+        segment_start = false;
+    }
+    keys
+}
+
+/// Escapes `label` for use in a quoted DOT string
+fn dot_escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Removes ANSI CSI escape sequences (`ESC [`, parameter and intermediate bytes and a final
+/// byte) from `line`. A lone `ESC` is removed together with the character following it.
+fn strip_ansi(line: &str) -> Cow<'_, str> {
+    if !line.contains('\x1b') {
+        return Cow::Borrowed(line);
+    }
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    Cow::Owned(stripped)
+}
+
+/// Builds a completed line from the matched `last_word` of `line` and the extension found in
+/// the tree. Leaves with a stored display form replace the last word entirely.
+fn completion(line: &str, last_word: &str, ext: &str, leaf: &CompletionNode) -> String {
+    match leaf.display() {
+        Some(display) => format!(
+            "{}{}",
+            line.strip_suffix(last_word).unwrap_or(line),
+            display
+        ),
+        None => format!("{}{}", line, ext),
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for CompletionTree {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CompletionTree::serialize(self, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for CompletionTree {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut tree = CompletionTree::deserialize(deserializer)?;
+        tree.word_count = tree.root.word_count();
+        if tree.substrings.is_some() {
+            tree.build_substring_index();
+        }
+        if tree.infixes.is_some() {
+            tree.build_infix_index();
+        }
+        tree.sync_recency();
+        Ok(tree)
+    }
+}
+
+/// Adds `word` to the substring index under every suffix of its stored form `stored`
+fn index_substrings(index: &mut SubstringIndex, stored: &str, word: &Arc<str>) {
+    for (i, _) in stored.char_indices() {
+        index_key(index, &stored[i..], word);
+    }
+}
+
+/// Removes `word` from the substring index, dropping suffixes no longer used by any word
+fn unindex_substrings(index: &mut SubstringIndex, stored: &str, word: &str) {
+    for (i, _) in stored.char_indices() {
+        unindex_key(index, &stored[i..], word);
+    }
+}
+
+/// Adds `word` to the infix index under every one of its segments
+fn index_infixes(index: &mut SubstringIndex, word: &Arc<str>) {
+    for key in infix_keys(word) {
+        index_key(index, &key, word);
+    }
+}
+
+/// Removes `word` from the infix index, dropping keys no longer used by any word
+fn unindex_infixes(index: &mut SubstringIndex, word: &str) {
+    for key in infix_keys(word) {
+        unindex_key(index, &key, word);
+    }
+}
+
+fn index_key(index: &mut SubstringIndex, key: &str, word: &Arc<str>) {
+    match index.get_mut(key) {
+        Some(words) => {
+            words.insert(Arc::clone(word));
+        }
+        None => {
+            index.insert(key, core::iter::once(Arc::clone(word)).collect());
+        }
+    }
+}
+
+fn unindex_key(index: &mut SubstringIndex, key: &str, word: &str) {
+    if let Some(words) = index.get_mut(key) {
+        words.remove(word);
+        if words.is_empty() {
+            index.remove(key);
+        }
+    }
+}
+
+/// Builds the completed lines for the alias `targets` completing `last_word`
+fn expansions(line: &str, last_word: &str, targets: &[&str]) -> Vec<String> {
+    let head = line.strip_suffix(last_word).unwrap_or(line);
+    targets
+        .iter()
+        .map(|target| format!("{}{}", head, target))
+        .collect()
+}
+
+/// Removes repeated completions, keeping the first occurrence of each, like an alias target
+/// that is also a word.
+fn dedup_completions(completions: &mut Vec<String>) {
+    let mut seen = BTreeSet::new();
+    completions.retain(|completion| seen.insert(completion.clone()));
+}
+
+/// Builds the completed word from the matched `last_word` and the extension found in the tree
+fn completed_word(last_word: &str, ext: &str, leaf: &CompletionNode) -> String {
+    match leaf.display() {
+        Some(display) => display.to_string(),
+        None => format!("{}{}", last_word, ext),
+    }
+}
+
+/// Iterates the leaves below a node in alphabetical order, yielding the path from the starting
+/// node together with the leaf
+#[derive(Debug)]
+struct Leaves<'a> {
+    stack: Vec<(String, &'a CompletionNode)>,
+}
+
+impl<'a> Leaves<'a> {
+    fn new(node: Option<&'a CompletionNode>) -> Self {
+        Self {
+            stack: node.map(|n| ("".to_string(), n)).into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> Iterator for Leaves<'a> {
+    type Item = (String, &'a CompletionNode);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((partial, node)) = self.stack.pop() {
+            for (c, subnode) in node.sorted_subnodes().rev() {
+                let mut partial = partial.clone();
+                partial.push(*c);
+                self.stack.push((partial, subnode));
+            }
+            if node.is_leaf() {
+                return Some((partial, node));
+            }
+        }
+        None
     }
 }
 
+#[cfg(not(feature = "fast-insert"))]
+type Subnodes = BTreeMap<char, CompletionNode>;
+#[cfg(feature = "fast-insert")]
+type Subnodes = HashMap<char, CompletionNode>;
+
+/// A node of a [CompletionTree], returned by [CompletionTree::subtree]
+///
+/// Each node is reached through a character from its parent. Nodes can be walked to
+/// implement custom traversal, ranking or rendering of the words in a tree.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CompletionNode {
+    subnodes: Subnodes,
+    leaf: Option<Box<LeafData>>,
+}
+
+/// The data of a word ending at a node, kept apart so nodes inside words stay small
 #[derive(Debug, Clone)]
-struct CompletionNode {
-    subnodes: BTreeMap<char, CompletionNode>,
-    leaf: bool,
-    inclusions: Arc<BTreeSet<char>>,
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct LeafData {
+    count: u32,
+    display: Option<String>,
 }
 
 impl CompletionNode {
-    fn new(incl: Arc<BTreeSet<char>>) -> Self {
+    fn new() -> Self {
         Self {
-            subnodes: BTreeMap::new(),
-            leaf: false,
-            inclusions: incl,
+            subnodes: Subnodes::default(),
+            leaf: None,
         }
     }
 
     fn clear(&mut self) {
         self.subnodes.clear();
+        self.leaf = None;
+    }
+
+    /// Returns the form the word ending at this node is completed to, if it isn't its path
+    fn display(&self) -> Option<&str> {
+        self.leaf.as_ref()?.display.as_deref()
+    }
+
+    /// Returns how many times the word ending at this node was inserted
+    fn count(&self) -> u32 {
+        self.leaf.as_ref().map_or(0, |leaf| leaf.count)
+    }
+
+    /// Iterates the subnodes in alphabetical order
+    #[cfg(not(feature = "fast-insert"))]
+    fn sorted_subnodes(&self) -> impl DoubleEndedIterator<Item = (&char, &CompletionNode)> {
+        self.subnodes.iter()
     }
 
-    fn word_count(&self) -> u32 {
+    /// Iterates the subnodes in alphabetical order
+    #[cfg(feature = "fast-insert")]
+    fn sorted_subnodes(&self) -> impl DoubleEndedIterator<Item = (&char, &CompletionNode)> {
+        let mut subnodes = self.subnodes.iter().collect::<Vec<_>>();
+        subnodes.sort_unstable_by_key(|(c, _)| **c);
+        subnodes.into_iter()
+    }
+
+    fn shrink_to_fit(&mut self) {
+        let subnodes = core::mem::take(&mut self.subnodes);
+        self.subnodes = subnodes
+            .into_iter()
+            .map(|(c, mut node)| {
+                node.shrink_to_fit();
+                (c, node)
+            })
+            .collect();
+        if let Some(display) = self.leaf.as_mut().and_then(|leaf| leaf.display.as_mut()) {
+            display.shrink_to_fit();
+        }
+    }
+
+    /// Returns an estimate of the heap memory owned by this node and its subnodes
+    fn heap_bytes(&self) -> usize {
+        let leaf = self.leaf.as_ref().map_or(0, |leaf| {
+            size_of::<LeafData>() + leaf.display.as_ref().map_or(0, |d| d.capacity())
+        });
+        if self.subnodes.is_empty() {
+            return leaf;
+        }
+        // Each map allocates nodes holding a parent pointer and lengths next to the entries
+        let overhead = 2 * size_of::<usize>();
+        let entries = self
+            .subnodes
+            .values()
+            .map(|node| size_of::<char>() + size_of::<CompletionNode>() + node.heap_bytes())
+            .sum::<usize>();
+        leaf + overhead + entries
+    }
+
+    /// Returns true if a word ends at this node
+    pub fn is_leaf(&self) -> bool {
+        self.leaf.is_some()
+    }
+
+    /// Returns an iterator over the child nodes together with the character leading to each
+    /// of them, in alphabetical order
+    pub fn children(&self) -> impl Iterator<Item = (char, &CompletionNode)> {
+        self.sorted_subnodes().map(|(c, node)| (*c, node))
+    }
+
+    /// Returns the child node reached through `c`, if any
+    pub fn child(&self, c: char) -> Option<&CompletionNode> {
+        self.subnodes.get(&c)
+    }
+
+    /// Returns a count of how many words end at or below this node
+    pub fn word_count(&self) -> u32 {
         let mut count = self.subnodes.values().map(|n| n.word_count()).sum();
-        if self.leaf {
+        if self.is_leaf() {
             count += 1;
         }
         count
     }
 
+    fn has_leaf(&self) -> bool {
+        self.is_leaf() || self.subnodes.values().any(|n| n.has_leaf())
+    }
+
+    /// Adds this node and its subnodes to `histogram`, this node being at `depth`
+    fn count_depths(&self, depth: usize, histogram: &mut Vec<u32>) {
+        if histogram.len() <= depth {
+            histogram.push(0);
+        }
+        histogram[depth] += 1;
+        for node in self.subnodes.values() {
+            node.count_depths(depth + 1, histogram);
+        }
+    }
+
     fn subnode_count(&self) -> u32 {
         self.subnodes
             .values()
@@ -301,48 +4558,352 @@ impl CompletionNode {
             + 1
     }
 
-    fn insert(&mut self, mut iter: Chars) {
-        if let Some(c) = iter.next() {
-            if self.inclusions.contains(&c) || c.is_alphanumeric() {
-                let inclusions = self.inclusions.clone();
-                let subnode = self
-                    .subnodes
-                    .entry(c)
-                    .or_insert_with(|| CompletionNode::new(inclusions));
-                subnode.insert(iter);
-            } else {
-                self.leaf = true;
+    fn insert(&mut self, iter: Chars, display: Option<&str>) -> bool {
+        self.insert_counted(iter, display, 1)
+    }
+
+    /// Inserts the word at the path `iter`, which the tree has already cut off at the first
+    /// character that isn't accepted, as if it was inserted `count` times. Returns true if the
+    /// word wasn't in the tree before.
+    fn insert_counted(&mut self, mut iter: Chars, display: Option<&str>, count: u32) -> bool {
+        match iter.next() {
+            Some(c) => self
+                .subnodes
+                .entry(c)
+                .or_insert_with(CompletionNode::new)
+                .insert_counted(iter, display, count),
+            None => self.mark_leaf(display, count),
+        }
+    }
+
+    /// Marks this node as the end of a word. Returns true if it wasn't already.
+    fn mark_leaf(&mut self, display: Option<&str>, count: u32) -> bool {
+        let added = self.leaf.is_none();
+        let leaf = self.leaf.get_or_insert_with(|| {
+            Box::new(LeafData {
+                count: 0,
+                display: None,
+            })
+        });
+        leaf.count = leaf.count.saturating_add(count);
+        if let Some(display) = display {
+            leaf.display = Some(display.to_string());
+        }
+        added
+    }
+
+    /// Removes the word at the exact path `iter`. Characters that aren't accepted never have
+    /// a node, so paths containing them aren't words and nothing is removed.
+    fn remove(&mut self, mut iter: Chars) -> bool {
+        match iter.next() {
+            Some(c) => {
+                if let Some(subnode) = self.subnodes.get_mut(&c) {
+                    let removed = subnode.remove(iter);
+                    if removed && !subnode.is_leaf() && subnode.subnodes.is_empty() {
+                        self.subnodes.remove(&c);
+                    }
+                    removed
+                } else {
+                    false
+                }
+            }
+            None => self.leaf.take().is_some(),
+        }
+    }
+
+    /// Removes this node and every node below it, returning the number of removed leaves
+    fn remove_prefix(&mut self, mut iter: Chars) -> u32 {
+        match iter.next() {
+            Some(c) => {
+                if let Some(subnode) = self.subnodes.get_mut(&c) {
+                    let removed = subnode.remove_prefix(iter);
+                    if !subnode.is_leaf() && subnode.subnodes.is_empty() {
+                        self.subnodes.remove(&c);
+                    }
+                    removed
+                } else {
+                    0
+                }
+            }
+            None => {
+                let removed = self.word_count();
+                self.clear();
+                removed
             }
-        } else {
-            self.leaf = true;
         }
     }
 
-    fn complete(&self, mut iter: Chars) -> Option<Vec<String>> {
+    /// Multiplies the count of every leaf by `factor`, rounding down.
+    fn scale_counts(&mut self, factor: f64) {
+        if let Some(leaf) = &mut self.leaf {
+            leaf.count = (f64::from(leaf.count) * factor) as u32;
+        }
+        self.subnodes
+            .values_mut()
+            .for_each(|node| node.scale_counts(factor));
+    }
+
+    /// Removes leaves for which `keep` returns false and prunes nodes left without words.
+    /// Returns the number of removed leaves.
+    fn retain_leaves<F>(&mut self, partial: &mut String, keep: &mut F) -> u32
+    where
+        F: FnMut(&str, &CompletionNode) -> bool,
+    {
+        let mut removed = 0;
+        if self.is_leaf() && !keep(partial, self) {
+            self.leaf = None;
+            removed += 1;
+        }
+        self.subnodes.retain(|c, node| {
+            partial.push(*c);
+            removed += node.retain_leaves(partial, keep);
+            partial.pop();
+            node.is_leaf() || !node.subnodes.is_empty()
+        });
+        removed
+    }
+
+    fn find(&self, mut iter: Chars) -> Option<&CompletionNode> {
         if let Some(c) = iter.next() {
             if let Some(subnode) = self.subnodes.get(&c) {
-                subnode.complete(iter)
+                subnode.find(iter)
             } else {
                 None
             }
         } else {
-            Some(self.collect("".to_string()))
+            Some(self)
         }
     }
 
-    fn collect(&self, partial: String) -> Vec<String> {
-        let mut completions = vec![];
-        if self.leaf {
-            completions.push(partial.clone());
+    fn collect_limited<'a>(
+        &'a self,
+        partial: String,
+        max: usize,
+        leaves: &mut Vec<(String, &'a CompletionNode)>,
+    ) {
+        if leaves.len() >= max {
+            return;
+        }
+        if self.is_leaf() {
+            leaves.push((partial.clone(), self));
+        }
+        for (c, node) in self.sorted_subnodes() {
+            if leaves.len() >= max {
+                break;
+            }
+            let mut partial = partial.clone();
+            partial.push(*c);
+            node.collect_limited(partial, max, leaves);
+        }
+    }
+
+    /// Writes this node and its subnodes as DOT statements to `out`. `partial` holds the word
+    /// this node represents.
+    fn write_dot(&self, partial: &mut String, next_id: &mut usize, out: &mut String) {
+        let id = *next_id;
+        *next_id += 1;
+        let shape = if self.is_leaf() {
+            "doublecircle"
+        } else {
+            "circle"
+        };
+        out.push_str(&format!(
+            "    n{} [label=\"{}\" shape={}];\n",
+            id,
+            dot_escape(partial),
+            shape
+        ));
+        for (c, node) in self.sorted_subnodes() {
+            out.push_str(&format!(
+                "    n{} -> n{} [label=\"{}\"];\n",
+                id,
+                next_id,
+                dot_escape(c.encode_utf8(&mut [0; 4]))
+            ));
+            partial.push(*c);
+            node.write_dot(partial, next_id, out);
+            partial.pop();
+        }
+    }
+
+    fn collect_leaves<'a>(
+        &'a self,
+        partial: String,
+        leaves: &mut Vec<(String, &'a CompletionNode)>,
+    ) {
+        if self.is_leaf() {
+            leaves.push((partial.clone(), self));
+        }
+        for (c, node) in self.sorted_subnodes() {
+            let mut partial = partial.clone();
+            partial.push(*c);
+            node.collect_leaves(partial, leaves);
+        }
+    }
+
+    /// Calls `visit` with every leaf at or below this node in order, together with `partial`
+    /// extended by the path to the leaf
+    fn visit_leaves<F>(&self, partial: &mut String, visit: &mut F)
+    where
+        F: FnMut(&str, &CompletionNode),
+    {
+        if self.is_leaf() {
+            visit(partial, self);
         }
+        for (c, node) in self.sorted_subnodes() {
+            partial.push(*c);
+            node.visit_leaves(partial, visit);
+            partial.pop();
+        }
+    }
+
+    /// Collects leaves at most `depth` levels below this node
+    fn collect_depth<'a>(
+        &'a self,
+        partial: String,
+        depth: usize,
+        leaves: &mut Vec<(String, &'a CompletionNode)>,
+    ) {
+        if self.is_leaf() {
+            leaves.push((partial.clone(), self));
+        }
+        if depth == 0 {
+            return;
+        }
+        for (c, node) in self.sorted_subnodes() {
+            let mut partial = partial.clone();
+            partial.push(*c);
+            node.collect_depth(partial, depth - 1, leaves);
+        }
+    }
+
+    /// Collects the nodes reached by walking `target` ignoring case, together with the paths
+    /// leading to them
+    fn find_ignore_case<'a>(
+        &'a self,
+        target: &[char],
+        partial: String,
+        found: &mut Vec<(String, &'a CompletionNode)>,
+    ) {
+        let (c, rest) = match target.split_first() {
+            Some(split) => split,
+            None => {
+                found.push((partial, self));
+                return;
+            }
+        };
+        for (k, node) in self.sorted_subnodes() {
+            if k == c || k.to_lowercase().eq(c.to_lowercase()) {
+                let mut partial = partial.clone();
+                partial.push(*k);
+                node.find_ignore_case(rest, partial, found);
+            }
+        }
+    }
+
+    /// Collects words with a prefix within `max` edits of `target`. `row` is the Levenshtein
+    /// row for the path leading to this node and `best` the lowest distance of any prefix
+    /// along that path.
+    fn collect_fuzzy<'a>(
+        &'a self,
+        target: &[char],
+        row: &[usize],
+        best: usize,
+        max: usize,
+        partial: String,
+        matches: &mut Vec<(usize, String, &'a CompletionNode)>,
+    ) {
+        if self.is_leaf() && best <= max {
+            matches.push((best, partial.clone(), self));
+        }
+        for (c, node) in &self.subnodes {
+            let mut next = Vec::with_capacity(row.len());
+            next.push(row[0] + 1);
+            for (i, t) in target.iter().enumerate() {
+                let substitution = row[i] + if t == c { 0 } else { 1 };
+                next.push(substitution.min(row[i + 1] + 1).min(next[i] + 1));
+            }
+            let best = best.min(next[target.len()]);
+            if best <= max || next.iter().min().is_some_and(|d| *d <= max) {
+                let mut partial = partial.clone();
+                partial.push(*c);
+                node.collect_fuzzy(target, &next, best, max, partial, matches);
+            }
+        }
+    }
 
-        if !self.subnodes.is_empty() {
-            for (c, node) in &self.subnodes {
+    /// Collects words within `max` edits of `target`. `row` is the Levenshtein row for the path
+    /// leading to this node.
+    fn collect_corrections<'a>(
+        &'a self,
+        target: &[char],
+        row: &[usize],
+        max: usize,
+        partial: String,
+        matches: &mut Vec<(usize, String, &'a CompletionNode)>,
+    ) {
+        if self.is_leaf() && row[target.len()] <= max {
+            matches.push((row[target.len()], partial.clone(), self));
+        }
+        for (c, node) in &self.subnodes {
+            let mut next = Vec::with_capacity(row.len());
+            next.push(row[0] + 1);
+            for (i, t) in target.iter().enumerate() {
+                let substitution = row[i] + if t == c { 0 } else { 1 };
+                next.push(substitution.min(row[i + 1] + 1).min(next[i] + 1));
+            }
+            if next.iter().min().is_some_and(|d| *d <= max) {
                 let mut partial = partial.clone();
                 partial.push(*c);
-                completions.append(&mut node.collect(partial));
+                node.collect_corrections(target, &next, max, partial, matches);
+            }
+        }
+    }
+
+    /// Adds the nodes whose path, continuing from `partial`, matches the glob `pattern` to
+    /// `matches` together with their path. `visited` holds the nodes already tried at each
+    /// position in the pattern, so each is tried once however many ways a `*` can reach it.
+    fn match_glob<'a>(
+        &'a self,
+        pattern: &[char],
+        partial: &mut String,
+        visited: &mut BTreeSet<(usize, usize)>,
+        matches: &mut Vec<(String, &'a CompletionNode)>,
+    ) {
+        if !visited.insert((self as *const Self as usize, pattern.len())) {
+            return;
+        }
+        let (first, rest) = match pattern.split_first() {
+            Some(split) => split,
+            None => return matches.push((partial.clone(), self)),
+        };
+        match first {
+            '*' => {
+                let rest = &pattern[pattern.iter().take_while(|c| **c == '*').count()..];
+                if rest.is_empty() {
+                    return matches.push((partial.clone(), self));
+                }
+                self.match_glob(rest, partial, visited, matches);
+                for (c, node) in self.sorted_subnodes() {
+                    partial.push(*c);
+                    node.match_glob(pattern, partial, visited, matches);
+                    partial.pop();
+                }
+            }
+            '?' => {
+                for (c, node) in self.sorted_subnodes() {
+                    partial.push(*c);
+                    node.match_glob(rest, partial, visited, matches);
+                    partial.pop();
+                }
+            }
+            c => {
+                if let Some(node) = self.subnodes.get(c) {
+                    partial.push(*c);
+                    node.match_glob(rest, partial, visited, matches);
+                    partial.pop();
+                }
             }
         }
-        completions
     }
 }
@@ -0,0 +1,166 @@
+use super::{CompletionTree, SortOrder, WordSeparator};
+
+/// A builder for configuring a [CompletionTree] in a single expression
+///
+/// Every option maps to the setter of the same name on [CompletionTree]. Options that aren't
+/// set keep the defaults of [CompletionTree::default].
+///
+/// # Example
+/// ```
+/// extern crate rs_complete;
+/// use rs_complete::{CompletionTreeBuilder, WordSeparator};
+///
+/// let mut completions = CompletionTreeBuilder::new()
+///     .inclusions(&['/', '_'])
+///     .separator(WordSeparator::Whitespace)
+///     .min_word_len(3)
+///     .case_insensitive(true)
+///     .build();
+/// completions.insert("/Batman bat_cave");
+/// assert_eq!(
+///     completions.complete("/bat"),
+///     Some(vec!["/Batman".to_string()]));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CompletionTreeBuilder {
+    tree: CompletionTree,
+}
+
+impl CompletionTreeBuilder {
+    /// Creates a builder with the default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the allowed non alphabet characters, see [CompletionTree::with_inclusions]
+    pub fn inclusions(mut self, incl: &[char]) -> Self {
+        self.tree.set_inclusions(incl);
+        self
+    }
+
+    /// Sets the excluded characters, see [CompletionTree::with_exclusions]
+    pub fn exclusions(mut self, excl: &[char]) -> Self {
+        self.tree.set_exclusions(excl);
+        self
+    }
+
+    /// Sets the word separator, see [CompletionTree::separator]
+    pub fn separator(mut self, separator: WordSeparator) -> Self {
+        self.tree.separator(separator);
+        self
+    }
+
+    /// Sets the minimum word length, see [CompletionTree::set_min_word_len]
+    pub fn min_word_len(mut self, len: usize) -> Self {
+        self.tree.set_min_word_len(len);
+        self
+    }
+
+    /// Sets the maximum word length, see [CompletionTree::set_max_word_len]
+    pub fn max_word_len(mut self, len: usize) -> Self {
+        self.tree.set_max_word_len(len);
+        self
+    }
+
+    /// Sets the minimum completion length, see [CompletionTree::set_min_completion_len]
+    pub fn min_completion_len(mut self, len: usize) -> Self {
+        self.tree.set_min_completion_len(len);
+        self
+    }
+
+    /// Enables or disables case insensitive matching, see
+    /// [CompletionTree::set_case_insensitive]
+    pub fn case_insensitive(mut self, enabled: bool) -> Self {
+        self.tree.set_case_insensitive(enabled);
+        self
+    }
+
+    /// Enables or disables NFC normalization, see [CompletionTree::set_normalize]
+    #[cfg(feature = "unicode-normalization")]
+    pub fn normalize(mut self, enabled: bool) -> Self {
+        self.tree.set_normalize(enabled);
+        self
+    }
+
+    /// Enables or disables diacritic insensitive matching, see
+    /// [CompletionTree::set_fold_diacritics]
+    #[cfg(feature = "unicode-normalization")]
+    pub fn fold_diacritics(mut self, enabled: bool) -> Self {
+        self.tree.set_fold_diacritics(enabled);
+        self
+    }
+
+    /// Enables or disables grapheme mode, see [CompletionTree::set_graphemes]
+    #[cfg(feature = "unicode-segmentation")]
+    pub fn graphemes(mut self, enabled: bool) -> Self {
+        self.tree.set_graphemes(enabled);
+        self
+    }
+
+    /// Enables or disables ANSI escape stripping, see [CompletionTree::set_strip_ansi]
+    pub fn strip_ansi(mut self, enabled: bool) -> Self {
+        self.tree.set_strip_ansi(enabled);
+        self
+    }
+
+    /// Enables or disables ignoring numbers, see [CompletionTree::set_ignore_numeric]
+    pub fn ignore_numeric(mut self, enabled: bool) -> Self {
+        self.tree.set_ignore_numeric(enabled);
+        self
+    }
+
+    /// Sets how words with characters that aren't accepted are handled, see
+    /// [CompletionTree::set_truncate_on_invalid]
+    pub fn truncate_on_invalid(mut self, enabled: bool) -> Self {
+        self.tree.set_truncate_on_invalid(enabled);
+        self
+    }
+
+    /// Enables or disables the strict character set, see [CompletionTree::set_strict_charset]
+    pub fn strict_charset(mut self, enabled: bool) -> Self {
+        self.tree.set_strict_charset(enabled);
+        self
+    }
+
+    /// Enables or disables sub-word indexing, see [CompletionTree::set_subword_indexing]
+    pub fn subword_indexing(mut self, enabled: bool) -> Self {
+        self.tree.set_subword_indexing(enabled);
+        self
+    }
+
+    /// Enables or disables completing empty lines, see [CompletionTree::set_complete_empty]
+    pub fn complete_empty(mut self, enabled: bool) -> Self {
+        self.tree.set_complete_empty(enabled);
+        self
+    }
+
+    /// Sets the maximum amount of completions, see [CompletionTree::set_max_results]
+    pub fn max_results(mut self, cap: Option<usize>) -> Self {
+        self.tree.set_max_results(cap);
+        self
+    }
+
+    /// Sets the order of completions, see [CompletionTree::set_sort_order]
+    pub fn sort_order(mut self, order: SortOrder) -> Self {
+        self.tree.set_sort_order(order);
+        self
+    }
+
+    /// Sets the maximum amount of words, see [CompletionTree::set_capacity]
+    pub fn capacity(mut self, max_words: Option<usize>) -> Self {
+        self.tree.set_capacity(max_words);
+        self
+    }
+
+    /// Tracks the order words are first inserted in, see
+    /// [CompletionTree::set_track_insertion_order]
+    pub fn track_insertion_order(mut self, enabled: bool) -> Self {
+        self.tree.set_track_insertion_order(enabled);
+        self
+    }
+
+    /// Builds the configured, empty, tree
+    pub fn build(self) -> CompletionTree {
+        self.tree
+    }
+}
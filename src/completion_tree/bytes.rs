@@ -0,0 +1,408 @@
+use super::{Aliases, CompletionNode, CompletionTree, LeafData, SortOrder, WordSeparator};
+use alloc::collections::{BTreeMap, BTreeSet};
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+use core::convert::TryInto;
+use core::fmt;
+use core::mem;
+
+const MAGIC: &[u8; 4] = b"RSCT";
+const VERSION: u8 = 1;
+
+/// Error returned by [CompletionTree::from_bytes] when data can't be decoded
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The data doesn't start with the expected magic number
+    InvalidMagic,
+    /// The data was encoded with an unsupported format version
+    UnsupportedVersion(u8),
+    /// The data ended before the tree was fully decoded
+    UnexpectedEof,
+    /// The data contains a value that isn't a valid unicode scalar
+    InvalidChar(u32),
+    /// The data contains a string that isn't valid UTF-8
+    InvalidUtf8,
+    /// The data contains an unknown separator type
+    InvalidSeparator(u8),
+    /// The data contains an unknown sort order
+    InvalidSortOrder(u8),
+    /// The data contains extra bytes after the tree
+    TrailingData,
+    /// The data contains a regex separator that can't be compiled. Only returned with the
+    /// `regex` feature enabled.
+    InvalidRegex,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::InvalidMagic => write!(f, "data is not a serialized completion tree"),
+            ParseError::UnsupportedVersion(v) => {
+                write!(f, "unsupported format version {} (expected {})", v, VERSION)
+            }
+            ParseError::UnexpectedEof => write!(f, "unexpected end of data"),
+            ParseError::InvalidChar(c) => write!(f, "invalid character {:#x}", c),
+            ParseError::InvalidUtf8 => write!(f, "invalid UTF-8 in string"),
+            ParseError::InvalidSeparator(s) => write!(f, "invalid separator type {}", s),
+            ParseError::InvalidSortOrder(s) => write!(f, "invalid sort order {}", s),
+            ParseError::TrailingData => write!(f, "unexpected data after tree"),
+            ParseError::InvalidRegex => write!(f, "invalid regex separator"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
+
+impl CompletionTree {
+    /// Encodes the tree, including its configuration, into a compact binary format that can
+    /// be restored using [CompletionTree::from_bytes]. The data starts with a magic number and
+    /// a format version so incompatible data can be detected.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::CompletionTree;
+    ///
+    /// let mut completions = CompletionTree::default();
+    /// completions.insert("batman robin batmobile");
+    /// let restored = CompletionTree::from_bytes(&completions.to_bytes()).unwrap();
+    /// assert_eq!(restored.words(), completions.words());
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        write_usize(&mut out, self.min_word_len);
+        write_option(&mut out, self.max_word_len, write_usize);
+        write_usize(&mut out, self.min_completion_len);
+        write_option(&mut out, self.max_results, write_usize);
+        out.push(self.case_insensitive as u8);
+        out.push(self.normalize as u8);
+        out.push(self.fold_diacritics as u8);
+        out.push(self.strip_ansi as u8);
+        out.push(self.ignore_numeric as u8);
+        out.push(self.subword_indexing as u8);
+        out.push(self.complete_empty as u8);
+        out.push(self.graphemes as u8);
+        out.push(self.truncate_on_invalid as u8);
+        out.push(self.strict_charset as u8);
+        write_option(&mut out, self.capacity, write_usize);
+        out.push(match self.sort_order {
+            SortOrder::Alphabetical => 0,
+            SortOrder::AlphabeticalReverse => 1,
+            SortOrder::ShortestFirst => 2,
+            SortOrder::LongestFirst => 3,
+        });
+        match &self.separator {
+            WordSeparator::Whitespace => out.push(0),
+            WordSeparator::Separator(sep) => {
+                out.push(1);
+                write_str(&mut out, sep);
+            }
+            WordSeparator::AnyOf(chars) => {
+                out.push(2);
+                write_usize(&mut out, chars.len());
+                chars.iter().for_each(|c| write_u32(&mut out, *c as u32));
+            }
+            WordSeparator::Owned(sep) => {
+                out.push(3);
+                write_str(&mut out, sep);
+            }
+            #[cfg(feature = "regex")]
+            WordSeparator::Regex(regex) => {
+                out.push(4);
+                write_str(&mut out, regex.as_str());
+            }
+        }
+        write_chars(&mut out, &self.inclusions);
+        write_chars(&mut out, &self.exclusions);
+        write_node(&mut out, &self.root);
+        write_option(&mut out, self.suffixes.as_ref(), write_node);
+        write_u64(&mut out, self.insert_seq);
+        write_option(&mut out, self.insert_order.as_ref(), write_insert_order);
+        out.push(self.substrings.is_some() as u8);
+        out.push(self.infixes.is_some() as u8);
+        write_aliases(&mut out, &self.aliases);
+        out
+    }
+
+    /// Decodes a tree previously encoded using [CompletionTree::to_bytes]. A custom
+    /// [WordSeparator::Separator] is decoded as the equivalent [WordSeparator::Owned].
+    ///
+    /// # Arguments
+    ///
+    /// * `data`    The encoded tree
+    ///
+    /// # Example
+    /// ```
+    /// extern crate rs_complete;
+    /// use rs_complete::{CompletionTree, ParseError};
+    ///
+    /// assert_eq!(
+    ///     CompletionTree::from_bytes(b"garbage").unwrap_err(),
+    ///     ParseError::InvalidMagic);
+    /// ```
+    pub fn from_bytes(data: &[u8]) -> Result<CompletionTree, ParseError> {
+        let mut reader = Reader { data };
+        if reader
+            .take(MAGIC.len())
+            .map_err(|_| ParseError::InvalidMagic)?
+            != MAGIC
+        {
+            return Err(ParseError::InvalidMagic);
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(ParseError::UnsupportedVersion(version));
+        }
+        let mut tree = CompletionTree {
+            min_word_len: reader.usize()?,
+            max_word_len: reader.option(Reader::usize)?,
+            min_completion_len: reader.usize()?,
+            max_results: reader.option(Reader::usize)?,
+            case_insensitive: reader.bool()?,
+            normalize: reader.bool()?,
+            fold_diacritics: reader.bool()?,
+            strip_ansi: reader.bool()?,
+            ignore_numeric: reader.bool()?,
+            subword_indexing: reader.bool()?,
+            complete_empty: reader.bool()?,
+            graphemes: reader.bool()?,
+            truncate_on_invalid: reader.bool()?,
+            strict_charset: reader.bool()?,
+            capacity: reader.option(Reader::usize)?,
+            sort_order: match reader.u8()? {
+                0 => SortOrder::Alphabetical,
+                1 => SortOrder::AlphabeticalReverse,
+                2 => SortOrder::ShortestFirst,
+                3 => SortOrder::LongestFirst,
+                s => return Err(ParseError::InvalidSortOrder(s)),
+            },
+            ..CompletionTree::default()
+        };
+        tree.separator = match reader.u8()? {
+            0 => WordSeparator::Whitespace,
+            1 | 3 => WordSeparator::Owned(reader.string()?),
+            2 => {
+                let len = reader.usize()?;
+                WordSeparator::AnyOf((0..len).map(|_| reader.char()).collect::<Result<_, _>>()?)
+            }
+            #[cfg(feature = "regex")]
+            4 => WordSeparator::Regex(
+                regex::Regex::new(&reader.string()?).map_err(|_| ParseError::InvalidRegex)?,
+            ),
+            s => return Err(ParseError::InvalidSeparator(s)),
+        };
+        tree.inclusions = reader.chars()?;
+        tree.exclusions = reader.chars()?;
+        tree.root = reader.node()?;
+        tree.word_count = tree.root.word_count();
+        tree.suffixes = reader.option(Reader::node)?;
+        tree.insert_seq = reader.u64()?;
+        tree.insert_order = reader.option(Reader::insert_order)?;
+        if reader.bool()? {
+            tree.build_substring_index();
+        }
+        if reader.bool()? {
+            tree.build_infix_index();
+        }
+        tree.aliases = reader.aliases()?;
+        tree.sync_recency();
+        if !reader.data.is_empty() {
+            return Err(ParseError::TrailingData);
+        }
+        Ok(tree)
+    }
+}
+
+fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_usize(out: &mut Vec<u8>, value: usize) {
+    write_u64(out, value as u64);
+}
+
+fn write_str(out: &mut Vec<u8>, value: &str) {
+    write_usize(out, value.len());
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn write_option<T>(out: &mut Vec<u8>, value: Option<T>, write: fn(&mut Vec<u8>, T)) {
+    match value {
+        Some(value) => {
+            out.push(1);
+            write(out, value);
+        }
+        None => out.push(0),
+    }
+}
+
+fn write_chars(out: &mut Vec<u8>, chars: &BTreeSet<char>) {
+    write_usize(out, chars.len());
+    chars.iter().for_each(|c| write_u32(out, *c as u32));
+}
+
+fn write_aliases(out: &mut Vec<u8>, aliases: &Aliases) {
+    write_usize(out, aliases.len());
+    for (alias, targets) in aliases {
+        write_str(out, alias);
+        write_usize(out, targets.len());
+        targets.iter().for_each(|target| write_str(out, target));
+    }
+}
+
+fn write_insert_order(out: &mut Vec<u8>, order: &BTreeMap<String, u64>) {
+    write_usize(out, order.len());
+    for (word, seq) in order {
+        write_str(out, word);
+        write_u64(out, *seq);
+    }
+}
+
+fn write_leaf(out: &mut Vec<u8>, leaf: &LeafData) {
+    write_u32(out, leaf.count);
+    write_option(out, leaf.display.as_deref(), write_str);
+}
+
+fn write_node(out: &mut Vec<u8>, node: &CompletionNode) {
+    write_option(out, node.leaf.as_deref(), write_leaf);
+    write_usize(out, node.subnodes.len());
+    for (c, subnode) in node.sorted_subnodes() {
+        write_u32(out, *c as u32);
+        write_node(out, subnode);
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ParseError> {
+        if self.data.len() < len {
+            return Err(ParseError::UnexpectedEof);
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, ParseError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, ParseError> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn u32(&mut self) -> Result<u32, ParseError> {
+        let bytes = self
+            .take(4)?
+            .try_into()
+            .map_err(|_| ParseError::UnexpectedEof)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, ParseError> {
+        let bytes = self
+            .take(8)?
+            .try_into()
+            .map_err(|_| ParseError::UnexpectedEof)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn usize(&mut self) -> Result<usize, ParseError> {
+        Ok(self.u64()? as usize)
+    }
+
+    fn char(&mut self) -> Result<char, ParseError> {
+        let value = self.u32()?;
+        core::char::from_u32(value).ok_or(ParseError::InvalidChar(value))
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        let len = self.usize()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| ParseError::InvalidUtf8)
+    }
+
+    fn aliases(&mut self) -> Result<Aliases, ParseError> {
+        let len = self.usize()?;
+        (0..len)
+            .map(|_| {
+                let alias = self.string()?;
+                let targets = self.usize()?;
+                let targets = (0..targets)
+                    .map(|_| self.string())
+                    .collect::<Result<BTreeSet<String>, ParseError>>()?;
+                Ok((alias, targets))
+            })
+            .collect()
+    }
+
+    fn insert_order(&mut self) -> Result<BTreeMap<String, u64>, ParseError> {
+        let len = self.usize()?;
+        (0..len)
+            .map(|_| Ok((self.string()?, self.u64()?)))
+            .collect()
+    }
+
+    fn option<T, F>(&mut self, mut read: F) -> Result<Option<T>, ParseError>
+    where
+        F: FnMut(&mut Self) -> Result<T, ParseError>,
+    {
+        if self.bool()? {
+            Ok(Some(read(self)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn chars(&mut self) -> Result<BTreeSet<char>, ParseError> {
+        let len = self.usize()?;
+        (0..len).map(|_| self.char()).collect()
+    }
+
+    fn leaf(&mut self) -> Result<Box<LeafData>, ParseError> {
+        Ok(Box::new(LeafData {
+            count: self.u32()?,
+            display: self.option(Reader::string)?,
+        }))
+    }
+
+    /// Reads a node and its subnodes. The nodes being read are kept on a stack rather than
+    /// decoded recursively, so words of any length decode without exhausting the call stack.
+    fn node(&mut self) -> Result<CompletionNode, ParseError> {
+        let (mut node, mut unread) = self.node_head()?;
+        // The parents of `node` with their unread subnodes and the character leading to the
+        // next one down
+        let mut parents = vec![];
+        loop {
+            if unread > 0 {
+                let c = self.char()?;
+                let (child, child_unread) = self.node_head()?;
+                parents.push((mem::replace(&mut node, child), unread - 1, c));
+                unread = child_unread;
+            } else if let Some((mut parent, parent_unread, c)) = parents.pop() {
+                parent.subnodes.insert(c, node);
+                node = parent;
+                unread = parent_unread;
+            } else {
+                return Ok(node);
+            }
+        }
+    }
+
+    /// Reads a node without its subnodes, returning it with the amount of subnodes to read
+    fn node_head(&mut self) -> Result<(CompletionNode, usize), ParseError> {
+        let mut node = CompletionNode::new();
+        node.leaf = self.option(Reader::leaf)?;
+        Ok((node, self.usize()?))
+    }
+}
@@ -0,0 +1,82 @@
+use super::CompletionTree;
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper, Result};
+
+/// A [rustyline](https://docs.rs/rustyline) helper completing words from a [CompletionTree]
+///
+/// The word in front of the cursor is completed, so completion also works with the cursor in
+/// the middle of a line. Requires the `rustyline` feature.
+///
+/// # Example
+/// ```no_run
+/// extern crate rs_complete;
+/// use rs_complete::{CompletionTree, TreeCompleter};
+/// use rustyline::history::DefaultHistory;
+/// use rustyline::Editor;
+///
+/// let mut completions = CompletionTree::default();
+/// completions.insert("batman robin batmobile");
+/// let mut editor = Editor::<TreeCompleter, DefaultHistory>::new().unwrap();
+/// editor.set_helper(Some(TreeCompleter::new(completions)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TreeCompleter {
+    tree: CompletionTree,
+}
+
+impl TreeCompleter {
+    /// Creates a helper completing words from `tree`
+    pub fn new(tree: CompletionTree) -> Self {
+        Self { tree }
+    }
+
+    /// Returns the tree used for completion
+    pub fn tree(&self) -> &CompletionTree {
+        &self.tree
+    }
+
+    /// Returns the tree used for completion, allowing words to be added while editing
+    pub fn tree_mut(&mut self) -> &mut CompletionTree {
+        &mut self.tree
+    }
+}
+
+impl Completer for TreeCompleter {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Result<(usize, Vec<Pair>)> {
+        // Positions inside a character, or past the end, complete nothing rather than panic
+        let head = match line.get(..pos) {
+            Some(head) => head,
+            None => return Ok((pos, vec![])),
+        };
+        let last_word = match self.tree.completable_word(head) {
+            Some(word) if head.ends_with(word) => word,
+            _ => return Ok((pos, vec![])),
+        };
+        let candidates = self
+            .tree
+            .complete_words(head)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|word| Pair {
+                display: word.clone(),
+                replacement: word,
+            })
+            .collect();
+        Ok((pos - last_word.len(), candidates))
+    }
+}
+
+impl Hinter for TreeCompleter {
+    type Hint = String;
+}
+
+impl Highlighter for TreeCompleter {}
+
+impl Validator for TreeCompleter {}
+
+impl Helper for TreeCompleter {}
@@ -37,16 +37,95 @@
 //!     completions.complete("bun"),
 //!     Some(vec!["bunch", "bundesliga", "bungalow"].iter().map(|s| s.to_string()).collect()));
 //! ```
+//!
+//! ## Features
+//!
+//! * `std`     Enabled by default. Without it the crate is `no_std` and only depends on `alloc`.
+//!   Everything except [CompletionTree::insert_reader] and the `std::error::Error`
+//!   implementations remains available
+//! * `serde`   Implements `Serialize` and `Deserialize` for [CompletionTree] and [WordSeparator]
+//! * `unicode-normalization`   Adds optional NFC normalization and diacritic insensitive
+//!   matching of words and input
+//! * `unicode-segmentation`    Adds [CompletionTree::set_graphemes] to keep grapheme clusters
+//!   like emoji sequences whole
+//! * `regex`   Adds [WordSeparator::Regex] to split words on a regular expression. Implies `std`
+//! * `fast-insert` Stores the children of each node in a `HashMap` rather than a `BTreeMap`,
+//!   which speeds up inserts at the cost of sorting children while completing. Implies `std`
+//! * `rayon`   Adds [CompletionTree::par_insert_all] to build trees in parallel. Implies `std`
+//! * `rustyline`   Adds `TreeCompleter`, a `rustyline` helper completing from a tree. Implies
+//!   `std`
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
-#[allow(dead_code)]
+mod completion_map;
 mod completion_tree;
 
+pub use completion_map::CompletionMap;
+pub use completion_tree::CompleteResult;
+pub use completion_tree::CompletionNode;
 pub use completion_tree::CompletionTree;
+pub use completion_tree::CompletionTreeBuilder;
+pub use completion_tree::InsertStats;
+pub use completion_tree::ParseError;
+pub use completion_tree::SortOrder;
+#[cfg(feature = "rustyline")]
+pub use completion_tree::TreeCompleter;
 pub use completion_tree::WordSeparator;
+pub use completion_tree::Words;
+
+use core::fmt;
+
+/// Error returned by [CompletionTree::try_complete] when it has no completions to return
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompleteError {
+    /// Nothing matches the input
+    NoMatch,
+    /// More words than requested match the input, holds the total amount of matches
+    TooMany(usize),
+}
+
+impl fmt::Display for CompleteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompleteError::NoMatch => write!(f, "no matches"),
+            CompleteError::TooMany(total) => write!(f, "too many matches ({})", total),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CompleteError {}
+
+/// Error returned by [CompletionTree::try_with_inclusions] for inclusions that have no effect
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InclusionError {
+    /// The character is alphanumeric and accepted without being included
+    Alphanumeric(char),
+    /// The character is whitespace, which ends words before they reach the tree
+    Whitespace(char),
+}
+
+impl fmt::Display for InclusionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InclusionError::Alphanumeric(c) => write!(f, "{:?} is already accepted", c),
+            InclusionError::Whitespace(c) => write!(f, "{:?} is whitespace", c),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InclusionError {}
 
 #[cfg(test)]
 mod tests {
-    use crate::{completion_tree::CompletionTree, WordSeparator};
+    use crate::{
+        completion_tree::CompletionTree, CompleteError, CompleteResult, CompletionMap,
+        CompletionNode, CompletionTreeBuilder, InclusionError, InsertStats, ParseError, SortOrder,
+        WordSeparator,
+    };
 
     #[test]
     fn test_completion() {
@@ -202,4 +281,2700 @@ mod tests {
         assert_eq!(completions.min_word_len(), 1);
         assert_eq!(completions.word_count(), 5);
     }
+
+    #[test]
+    fn test_remove() {
+        let mut completions = CompletionTree::default();
+        completions.insert("batman batmobile batcave robin");
+        assert_eq!(completions.word_count(), 4);
+        assert_eq!(completions.size(), 21);
+        assert!(completions.remove("batman"));
+        assert_eq!(completions.word_count(), 3);
+        assert_eq!(completions.size(), 19);
+        assert!(!completions.remove("batman"));
+        assert!(!completions.remove("bat"));
+        assert_eq!(completions.word_count(), 3);
+        assert_eq!(
+            completions.complete("batm"),
+            Some(vec!["batmobile".to_string()])
+        );
+        assert!(completions.remove("robin"));
+        assert_eq!(completions.size(), 14);
+    }
+
+    #[test]
+    fn test_contains() {
+        let mut completions = CompletionTree::default();
+        completions.insert("dumpsterfire");
+        assert!(completions.contains("dumpsterfire"));
+        assert!(!completions.contains("dumpster"));
+        completions.insert("dumpster");
+        assert!(completions.contains("dumpster"));
+        assert!(!completions.contains("dumpsterf"));
+
+        let mut completions = CompletionTree::with_inclusions(&['/', '_']);
+        completions.insert("/dumpster_fire");
+        assert!(completions.contains("/dumpster_fire"));
+        assert!(!completions.contains("dumpster_fire"));
+    }
+
+    #[test]
+    fn test_complete_limited() {
+        let mut completions = CompletionTree::default();
+        completions.insert("batman robin batmobile batcave robber");
+        assert_eq!(
+            completions.complete_limited("bat", 2),
+            Some(vec!["batcave".to_string(), "batman".to_string()])
+        );
+        assert_eq!(
+            completions.complete_limited("to the bat", 10),
+            completions.complete("to the bat")
+        );
+        assert_eq!(completions.complete_limited("bat", 0), None);
+        assert_eq!(completions.complete_limited("joker", 2), None);
+        assert_eq!(completions.complete_limited("", 2), None);
+    }
+
+    #[test]
+    fn test_complete_ranked() {
+        let mut completions = CompletionTree::default();
+        completions.set_min_word_len(1);
+        completions.insert("go go go gold");
+        assert_eq!(
+            completions.complete_ranked("g"),
+            Some(vec!["go".to_string(), "gold".to_string()])
+        );
+        assert_eq!(
+            completions.complete("g"),
+            Some(vec!["go".to_string(), "gold".to_string()])
+        );
+        completions.insert("gold gold gold");
+        assert_eq!(
+            completions.complete_ranked("g"),
+            Some(vec!["gold".to_string(), "go".to_string()])
+        );
+        assert_eq!(completions.complete_ranked("x"), None);
+
+        completions.add_alias("gd", "goad");
+        assert_eq!(
+            completions.complete_ranked("g"),
+            Some(vec![
+                "goad".to_string(),
+                "gold".to_string(),
+                "go".to_string()
+            ])
+        );
+        completions.set_max_results(Some(1));
+        assert_eq!(completions.complete_ranked("go"), None);
+        assert_eq!(
+            completions.complete_ranked("gol"),
+            Some(vec!["gold".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let mut completions = CompletionTree::default();
+        completions.insert("Batman");
+        assert_eq!(completions.complete("BAT"), None);
+
+        let mut completions = CompletionTree::with_inclusions(&['_']);
+        completions.set_case_insensitive(true);
+        assert!(completions.case_insensitive());
+        completions.insert("Batman batmobile BAT_CAVE");
+        assert_eq!(
+            completions.complete("BAT"),
+            Some(vec![
+                "BAT_CAVE".to_string(),
+                "Batman".to_string(),
+                "batmobile".to_string()
+            ])
+        );
+        assert_eq!(
+            completions.complete("go bAtM"),
+            Some(vec!["go Batman".to_string(), "go batmobile".to_string()])
+        );
+        assert!(completions.contains("BATMAN"));
+        assert!(completions.remove("bat_cave"));
+        assert_eq!(completions.word_count(), 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut completions = CompletionTree::with_inclusions(&['/', '_']);
+        completions.separator(WordSeparator::Separator("|"));
+        completions.insert("/batman|/batmobile|bat_cave|robin");
+        let json = serde_json::to_string(&completions).unwrap();
+        let mut restored: CompletionTree = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.word_count(), 4);
+        assert_eq!(restored.complete("/bat"), completions.complete("/bat"));
+        assert_eq!(restored.complete("bat"), completions.complete("bat"));
+
+        restored.insert("/joker|harley_quinn");
+        assert!(restored.contains("/joker"));
+        assert!(restored.contains("harley_quinn"));
+
+        let separator: WordSeparator = serde_json::from_str(r#"{"Separator":"|"}"#).unwrap();
+        assert_eq!(separator, WordSeparator::Owned("|".to_string()));
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let words = vec!["batman robin", "batmobile", "bat"];
+        let completions: CompletionTree = words.into_iter().collect();
+        assert_eq!(completions.word_count(), 3);
+        assert_eq!(completions.min_word_len(), 5);
+
+        let words = vec!["batman".to_string(), "batcave robber".to_string()];
+        let completions: CompletionTree = words.into_iter().collect();
+        assert_eq!(completions.word_count(), 3);
+        assert!(completions.contains("robber"));
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut completions = CompletionTree::default();
+        completions.separator(WordSeparator::Separator("&"));
+        completions.set_min_word_len(3);
+        completions.extend(vec!["batman&robin", "bat&go"]);
+        assert_eq!(completions.word_count(), 3);
+        completions.extend(vec!["batcave&robber".to_string()]);
+        assert_eq!(completions.word_count(), 5);
+        assert!(completions.contains("bat"));
+        assert!(!completions.contains("go"));
+    }
+
+    #[test]
+    fn test_words() {
+        let mut completions = CompletionTree::default();
+        assert!(completions.words().is_empty());
+        completions.insert("robin batmobile batman");
+        assert_eq!(
+            completions.words(),
+            vec![
+                "batman".to_string(),
+                "batmobile".to_string(),
+                "robin".to_string()
+            ]
+        );
+        completions.clear();
+        assert!(completions.words().is_empty());
+    }
+
+    #[test]
+    fn test_complete_custom_sep() {
+        let mut tree = CompletionTree::default();
+        tree.separator(WordSeparator::Separator("|"));
+        tree.insert("send|batman|batmobile");
+        assert_eq!(
+            tree.complete("send|bat"),
+            Some(vec![
+                "send|batman".to_string(),
+                "send|batmobile".to_string()
+            ])
+        );
+        assert_eq!(tree.complete("send bat"), None);
+    }
+
+    #[test]
+    fn test_min_completion_len() {
+        let mut completions = CompletionTree::default();
+        completions.insert("batman robin");
+        assert_eq!(completions.min_completion_len(), 1);
+        assert_eq!(completions.complete("b"), Some(vec!["batman".to_string()]));
+
+        completions.set_min_completion_len(5);
+        assert_eq!(completions.min_completion_len(), 5);
+        assert_eq!(completions.complete("batm"), None);
+        assert_eq!(completions.complete("to the batm"), None);
+        assert_eq!(
+            completions.complete("batma"),
+            Some(vec!["batman".to_string()])
+        );
+
+        completions.set_min_completion_len(3);
+        completions.insert("ébène");
+        assert_eq!(completions.complete("éb"), None);
+        assert_eq!(completions.complete("ébè"), Some(vec!["ébène".to_string()]));
+    }
+
+    #[test]
+    fn test_complete_words() {
+        let mut completions = CompletionTree::default();
+        completions.insert("batman robin batmobile");
+        assert_eq!(
+            completions.complete("to the batm"),
+            Some(vec![
+                "to the batman".to_string(),
+                "to the batmobile".to_string()
+            ])
+        );
+        assert_eq!(
+            completions.complete_words("to the batm"),
+            Some(vec!["batman".to_string(), "batmobile".to_string()])
+        );
+        assert_eq!(completions.complete_words("to the joker"), None);
+    }
+
+    #[test]
+    fn test_complete_fuzzy() {
+        let mut completions = CompletionTree::default();
+        completions.insert("batman robin batmobile batcave robber");
+        assert_eq!(
+            completions.complete_fuzzy("btman", 1),
+            Some(vec!["batman".to_string()])
+        );
+        assert_eq!(
+            completions.complete_fuzzy("rbin", 1),
+            Some(vec!["robin".to_string()])
+        );
+        assert_eq!(completions.complete_fuzzy("btmn", 1), None);
+        assert_eq!(
+            completions.complete_fuzzy("btmn", 2),
+            Some(vec!["batman".to_string(), "batmobile".to_string()])
+        );
+        assert_eq!(
+            completions.complete_fuzzy("batm", 0),
+            completions.complete("batm")
+        );
+        assert_eq!(completions.complete_fuzzy("", 2), None);
+    }
+
+    #[test]
+    fn test_max_word_len() {
+        let mut completions = CompletionTree::default();
+        assert_eq!(completions.max_word_len(), None);
+        completions.set_max_word_len(10);
+        completions.insert("batman aGVsbG8gd29ybGQgYmxvYg robin dGhpcyBpcyBhIGxvbmcgYmxvYg==");
+        assert_eq!(completions.max_word_len(), Some(10));
+        assert_eq!(completions.word_count(), 2);
+        assert!(completions.contains("batman"));
+        assert!(completions.contains("robin"));
+    }
+
+    #[test]
+    fn test_update_inclusions() {
+        let mut tree = CompletionTree::default();
+        tree.insert("dumpster_fire");
+        tree.add_inclusion('_');
+        tree.insert("dumpster_truck");
+        assert_eq!(
+            tree.complete("dump"),
+            Some(vec!["dumpster".to_string(), "dumpster_truck".to_string()])
+        );
+
+        tree.set_inclusions(&['@', '/']);
+        tree.insert("@batman /robin_hood");
+        assert!(tree.contains("@batman"));
+        assert_eq!(tree.complete("/rob"), Some(vec!["/robin".to_string()]));
+    }
+
+    #[test]
+    fn test_with_exclusions() {
+        let digits = ('0'..='9').collect::<Vec<char>>();
+        let mut tree = CompletionTree::with_exclusions(&digits);
+        tree.insert("port8080 http2server");
+        assert!(tree.contains("port"));
+        assert!(!tree.contains("port8080"));
+        assert_eq!(tree.complete("ht"), Some(vec!["http".to_string()]));
+
+        tree.set_inclusions(&['2']);
+        tree.insert("http2server");
+        assert_eq!(
+            tree.complete("http2"),
+            Some(vec!["http2server".to_string()])
+        );
+
+        tree.set_exclusions(&[]);
+        tree.insert("port8080");
+        assert!(tree.contains("port8080"));
+    }
+
+    #[test]
+    fn test_longest_common_prefix() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman batmobile robin robber");
+        assert_eq!(tree.longest_common_prefix("bat"), Some("batm".to_string()));
+        assert_eq!(tree.longest_common_prefix("b"), Some("batm".to_string()));
+        assert_eq!(tree.longest_common_prefix("r"), Some("rob".to_string()));
+        assert_eq!(
+            tree.longest_common_prefix("batma"),
+            Some("batman".to_string())
+        );
+        assert_eq!(
+            tree.longest_common_prefix("batman"),
+            Some("batman".to_string())
+        );
+        assert_eq!(tree.longest_common_prefix("joker"), None);
+        assert_eq!(tree.longest_common_prefix(""), None);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batcave");
+        let mut other = CompletionTree::default();
+        other.set_min_word_len(1);
+        other.insert("robin batmobile joker go");
+        tree.merge(&other);
+        assert_eq!(other.word_count(), 4);
+        assert_eq!(tree.word_count(), 5);
+        assert_eq!(
+            tree.words(),
+            ["batcave", "batman", "batmobile", "joker", "robin"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn test_completion_map() {
+        let mut map = CompletionMap::default();
+        assert_eq!(map.insert("batman", "bruce"), None);
+        assert_eq!(map.insert("batgirl", "barbara"), None);
+        assert_eq!(map.insert("robin", "dick"), None);
+        assert_eq!(map.insert("robin", "jason"), Some("dick"));
+        assert_eq!(map.word_count(), 3);
+        assert_eq!(map.get("robin"), Some(&"jason"));
+        assert_eq!(map.get("rob"), None);
+        assert_eq!(
+            map.complete("bat"),
+            Some(vec![
+                ("batgirl".to_string(), &"barbara"),
+                ("batman".to_string(), &"bruce")
+            ])
+        );
+        assert_eq!(map.remove("batgirl"), Some("barbara"));
+        assert_eq!(map.word_count(), 2);
+        assert_eq!(map.complete("batg"), None);
+        assert_eq!(map.complete(""), None);
+        map.clear();
+        assert_eq!(map.word_count(), 0);
+    }
+
+    #[test]
+    fn test_top_k() {
+        let mut tree = CompletionTree::default();
+        tree.set_min_word_len(1);
+        tree.insert("go go go gold gold gone goal goal goal goal");
+        assert_eq!(
+            tree.top_k("g", 2),
+            Some(vec!["goal".to_string(), "go".to_string()])
+        );
+        assert_eq!(
+            tree.top_k("run g", 5),
+            Some(vec![
+                "run goal".to_string(),
+                "run go".to_string(),
+                "run gold".to_string(),
+                "run gone".to_string()
+            ])
+        );
+        tree.insert("gone");
+        assert_eq!(tree.top_k("gol", 3), Some(vec!["gold".to_string()]));
+        assert_eq!(tree.top_k("gon", 0), None);
+        assert_eq!(tree.top_k("x", 3), None);
+    }
+
+    #[test]
+    fn test_prune_below() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile batcave robber");
+        tree.insert("batman batman robin batcave");
+        assert_eq!(tree.size(), 24);
+        assert_eq!(tree.prune_below(2), 2);
+        assert_eq!(
+            tree.words(),
+            vec![
+                "batcave".to_string(),
+                "batman".to_string(),
+                "robin".to_string()
+            ]
+        );
+        assert_eq!(tree.size(), 16);
+        assert_eq!(tree.prune_below(3), 2);
+        assert_eq!(tree.words(), vec!["batman".to_string()]);
+        assert_eq!(tree.size(), 7);
+        assert_eq!(tree.prune_below(3), 0);
+    }
+
+    #[test]
+    fn test_complete_iter() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile batcave robber");
+        assert_eq!(
+            tree.complete_iter("to the bat").collect::<Vec<String>>(),
+            tree.complete("to the bat").unwrap()
+        );
+        assert_eq!(
+            tree.complete_iter("r").take(1).collect::<Vec<String>>(),
+            vec!["robber".to_string()]
+        );
+        assert_eq!(tree.complete_iter("joker").next(), None);
+        assert_eq!(tree.complete_iter("").next(), None);
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn test_normalize() {
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+
+        let mut tree = CompletionTree::default();
+        tree.set_min_word_len(1);
+        tree.insert(composed);
+        tree.insert(decomposed);
+        assert_eq!(tree.word_count(), 2);
+
+        let mut tree = CompletionTree::default();
+        tree.set_min_word_len(1);
+        tree.set_normalize(true);
+        assert!(tree.normalize());
+        tree.insert(composed);
+        tree.insert(decomposed);
+        assert_eq!(tree.word_count(), 1);
+        assert!(tree.contains(decomposed));
+        assert_eq!(tree.complete("caf"), Some(vec![composed.to_string()]));
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn test_normalize_toggle() {
+        let mut tree = CompletionTree::with_inclusions(&['\u{301}']);
+        tree.insert("re\u{301}sume\u{301}");
+        tree.set_normalize(true);
+        assert_eq!(
+            tree.complete("r\u{e9}s"),
+            Some(vec!["r\u{e9}sum\u{e9}".to_string()])
+        );
+        assert!(tree.contains("re\u{301}sume\u{301}"));
+        assert!(tree.remove("re\u{301}sume\u{301}"));
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_complete_suffix() {
+        let mut tree = CompletionTree::default();
+        tree.insert("running jumping batman");
+        assert_eq!(tree.complete_suffix("ing"), None);
+
+        tree.build_suffix_index();
+        tree.insert("singing");
+        assert_eq!(
+            tree.complete_suffix("ing"),
+            Some(vec![
+                "jumping".to_string(),
+                "running".to_string(),
+                "singing".to_string()
+            ])
+        );
+        assert_eq!(
+            tree.complete_suffix("man"),
+            Some(vec!["batman".to_string()])
+        );
+        assert!(tree.remove("running"));
+        assert_eq!(tree.complete_suffix("nning"), None);
+        assert_eq!(tree.complete_suffix("xyz"), None);
+        tree.insert("singing");
+        assert_eq!(tree.prune_below(2), 2);
+        assert_eq!(
+            tree.complete_suffix("ing"),
+            Some(vec!["singing".to_string()])
+        );
+        tree.clear();
+        assert_eq!(tree.complete_suffix("ing"), None);
+
+        let mut tree = CompletionTree::with_inclusions(&['-']);
+        tree.build_suffix_index();
+        tree.insert("bat-man");
+        tree.set_inclusions(&[]);
+        assert!(tree.remove("bat-man"));
+        assert!(tree.words().is_empty());
+        assert_eq!(tree.complete_suffix("man"), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_insert_reader() {
+        let data = b"batman robin\r\nbatmobile\n\nbatcave|robber";
+        let mut tree = CompletionTree::default();
+        tree.insert_reader(std::io::Cursor::new(&data[..])).unwrap();
+        assert_eq!(tree.word_count(), 4);
+
+        let mut tree = CompletionTree::default();
+        tree.separator(WordSeparator::Separator("|"));
+        tree.insert_reader(std::io::Cursor::new(&data[..])).unwrap();
+        assert_eq!(
+            tree.words(),
+            vec![
+                "batcave".to_string(),
+                "batman".to_string(),
+                "batmobile".to_string(),
+                "robber".to_string()
+            ]
+        );
+
+        let invalid = [0x66, 0x6f, 0x6f, 0xff, 0xfe];
+        let mut tree = CompletionTree::default();
+        assert!(tree
+            .insert_reader(std::io::Cursor::new(&invalid[..]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let mut tree = CompletionTree::with_inclusions(&['/', '_']);
+        tree.separator(WordSeparator::Separator("|"));
+        tree.set_case_insensitive(true);
+        tree.set_max_word_len(12);
+        tree.insert("/Batman|/batmobile|bat_cave|robin|batmobile");
+        tree.build_suffix_index();
+        let data = tree.to_bytes();
+        let mut restored = CompletionTree::from_bytes(&data).unwrap();
+        assert_eq!(restored.words(), tree.words());
+        assert_eq!(restored.max_word_len(), Some(12));
+        assert_eq!(restored.complete_ranked("/b"), tree.complete_ranked("/b"));
+        assert_eq!(
+            restored.complete_suffix("obile"),
+            tree.complete_suffix("obile")
+        );
+        restored.insert("/Joker|harley_quinn");
+        assert!(restored.contains("/joker"));
+        assert!(restored.contains("harley_quinn"));
+
+        assert_eq!(
+            CompletionTree::from_bytes(&data[..data.len() - 1]).unwrap_err(),
+            ParseError::UnexpectedEof
+        );
+        let mut data = data;
+        assert_eq!(data[4], 1);
+        data[4] = 99;
+        assert_eq!(
+            CompletionTree::from_bytes(&data).unwrap_err(),
+            ParseError::UnsupportedVersion(99)
+        );
+        assert_eq!(
+            CompletionTree::from_bytes(b"RS").unwrap_err(),
+            ParseError::InvalidMagic
+        );
+    }
+
+    #[test]
+    fn test_count_completions() {
+        let mut tree = CompletionTree::default();
+        assert_eq!(tree.count_completions("bat"), 0);
+        tree.insert("batman robin batmobile batcave robber");
+        assert_eq!(tree.count_completions("b"), 3);
+        assert_eq!(tree.count_completions("to the rob"), 2);
+        assert_eq!(tree.count_completions("batman"), 1);
+        assert_eq!(tree.count_completions("joker"), 0);
+        assert_eq!(tree.count_completions(""), 0);
+        assert_eq!(
+            tree.count_completions("bat"),
+            tree.complete("bat").unwrap().len()
+        );
+    }
+
+    #[test]
+    fn test_has_completions() {
+        let mut tree = CompletionTree::default();
+        assert!(!tree.has_completions("bat"));
+        tree.insert("batman robin");
+        assert!(tree.has_completions("b"));
+        assert!(tree.has_completions("to the rob"));
+        assert!(tree.has_completions("batman"));
+        assert!(!tree.has_completions("batmen"));
+        assert!(!tree.has_completions(""));
+        tree.remove("batman");
+        assert!(!tree.has_completions("bat"));
+
+        tree.add_alias("btm", "batmobile");
+        assert!(tree.has_completions("bt"));
+        assert_eq!(tree.complete("bt"), Some(vec!["batmobile".to_string()]));
+
+        tree.insert("robber robbie");
+        tree.set_max_results(Some(2));
+        assert_eq!(tree.complete("rob"), None);
+        assert!(!tree.has_completions("rob"));
+        assert!(tree.has_completions("robb"));
+    }
+
+    #[test]
+    fn test_complete_max_depth() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile batcave robber");
+        assert_eq!(
+            tree.complete_max_depth("to the bat", 4),
+            Some(vec![
+                "to the batcave".to_string(),
+                "to the batman".to_string()
+            ])
+        );
+        assert_eq!(tree.complete_max_depth("bat", 6), tree.complete("bat"));
+        assert_eq!(
+            tree.complete_max_depth("robin", 0),
+            Some(vec!["robin".to_string()])
+        );
+        assert_eq!(tree.complete_max_depth("bat", 2), Some(vec![]));
+        assert_eq!(tree.complete_max_depth("joker", 2), None);
+
+        tree.add_alias("btm", "batmobile");
+        assert_eq!(
+            tree.complete_max_depth("bt", 4),
+            Some(vec!["batmobile".to_string()])
+        );
+        tree.set_max_results(Some(1));
+        assert_eq!(tree.complete_max_depth("bat", 4), None);
+        assert_eq!(
+            tree.complete_max_depth("rob", 2),
+            Some(vec!["robin".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_complete_by() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batmobile batman batcave batarang");
+        assert_eq!(
+            tree.complete_by("to the bat", |a, b| a.len().cmp(&b.len()).then(a.cmp(b))),
+            Some(vec![
+                "to the batman".to_string(),
+                "to the batcave".to_string(),
+                "to the batarang".to_string(),
+                "to the batmobile".to_string(),
+            ])
+        );
+        assert_eq!(tree.complete_by("joker", |a, b| a.cmp(b)), None);
+    }
+
+    #[test]
+    fn test_complete_by_insertion_order() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batmobile batman");
+        assert_eq!(
+            tree.complete_by_insertion_order("bat"),
+            Some(vec!["batman".to_string(), "batmobile".to_string()])
+        );
+        tree.clear();
+        tree.set_track_insertion_order(true);
+        assert!(tree.tracks_insertion_order());
+        tree.insert("robin batmobile batman");
+        tree.insert("batcave batman");
+        assert_eq!(
+            tree.complete_by_insertion_order("to the bat"),
+            Some(vec![
+                "to the batmobile".to_string(),
+                "to the batman".to_string(),
+                "to the batcave".to_string(),
+            ])
+        );
+        tree.remove("batmobile");
+        tree.insert("batmobile");
+        assert_eq!(
+            tree.complete_by_insertion_order("bat"),
+            Some(vec![
+                "batman".to_string(),
+                "batcave".to_string(),
+                "batmobile".to_string(),
+            ])
+        );
+        let restored = CompletionTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert_eq!(
+            restored.complete_by_insertion_order("bat"),
+            tree.complete_by_insertion_order("bat")
+        );
+        tree.clear();
+        tree.insert("batcave batman");
+        assert_eq!(
+            tree.complete_by_insertion_order("bat"),
+            Some(vec!["batcave".to_string(), "batman".to_string()])
+        );
+        tree.set_track_insertion_order(false);
+        assert!(!tree.tracks_insertion_order());
+        assert_eq!(
+            tree.complete_by_insertion_order("bat"),
+            Some(vec!["batcave".to_string(), "batman".to_string()])
+        );
+
+        tree.add_alias("btm", "batmobile");
+        assert_eq!(
+            tree.complete_by_insertion_order("bt"),
+            Some(vec!["batmobile".to_string()])
+        );
+        tree.set_max_results(Some(1));
+        assert_eq!(tree.complete_by_insertion_order("bat"), None);
+    }
+
+    #[test]
+    fn test_complete_phrase() {
+        let mut tree = CompletionTree::with_inclusions(&[' ']);
+        tree.separator(WordSeparator::Separator(","));
+        tree.insert("cast fire bolt,cast fire ball,cast frost nova,fire walk");
+        assert_eq!(
+            tree.complete_phrase("I will  cast fi", 2),
+            Some(vec![
+                "I will  cast fire ball".to_string(),
+                "I will  cast fire bolt".to_string(),
+            ])
+        );
+        assert_eq!(
+            tree.complete_phrase("cast   fire b", 3),
+            Some(vec![
+                "cast fire ball".to_string(),
+                "cast fire bolt".to_string(),
+            ])
+        );
+        assert_eq!(
+            tree.complete_phrase("cast fire", 1),
+            Some(vec!["cast fire walk".to_string()])
+        );
+        assert_eq!(
+            tree.complete_phrase("cast fr", 5),
+            Some(vec!["cast frost nova".to_string()])
+        );
+        assert_eq!(tree.complete_phrase("cast fire", 0), None);
+        assert_eq!(tree.complete_phrase("   ", 2), None);
+        assert_eq!(tree.complete_phrase("cast ice", 2), None);
+
+        tree.set_min_completion_len(4);
+        tree.insert("été chaud");
+        assert_eq!(tree.complete_phrase("été", 2), None);
+        assert_eq!(
+            tree.complete_phrase("été c", 2),
+            Some(vec!["été chaud".to_string()])
+        );
+
+        tree.set_min_completion_len(0);
+        tree.add_alias("cfb", "cast fire bolt");
+        assert_eq!(
+            tree.complete_phrase("now cfb", 1),
+            Some(vec!["now cast fire bolt".to_string()])
+        );
+        tree.add_alias("cast f", "cast fire ball");
+        assert_eq!(
+            tree.complete_phrase("cast f", 2),
+            Some(vec![
+                "cast fire ball".to_string(),
+                "cast fire bolt".to_string(),
+                "cast frost nova".to_string(),
+            ])
+        );
+        tree.set_max_results(Some(1));
+        assert_eq!(tree.complete_phrase("cast fire b", 2), None);
+        assert_eq!(
+            tree.complete_phrase("cast fr", 2),
+            Some(vec!["cast frost nova".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        let mut tree = CompletionTree::default();
+        tree.insert("red\x1b[0mword");
+        assert_eq!(tree.words(), vec!["red".to_string()]);
+
+        let mut tree = CompletionTree::default();
+        tree.set_strip_ansi(true);
+        assert!(tree.strip_ansi());
+        tree.insert("red\x1b[0mword \x1b[1;34mdumpster\x1b[0m\x1b[2Kfire\x1b[m batman\x1b");
+        assert_eq!(
+            tree.words(),
+            vec![
+                "batman".to_string(),
+                "dumpsterfire".to_string(),
+                "redword".to_string(),
+            ]
+        );
+        let restored = CompletionTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert!(restored.strip_ansi());
+    }
+
+    #[test]
+    fn test_ignore_numeric() {
+        let mut tree = CompletionTree::default();
+        tree.set_min_word_len(3);
+        tree.insert("404");
+        assert!(tree.contains("404"));
+
+        let mut tree = CompletionTree::default();
+        tree.set_min_word_len(3);
+        tree.set_ignore_numeric(true);
+        assert!(tree.ignore_numeric());
+        tree.insert("404 http2 12345 x86 \u{661}\u{662}\u{663}");
+        assert!(!tree.contains("404"));
+        assert!(!tree.contains("12345"));
+        assert!(tree.contains("http2"));
+        assert!(tree.contains("x86"));
+        assert!(tree.contains("\u{661}\u{662}\u{663}"));
+        assert_eq!(tree.word_count(), 3);
+    }
+
+    #[test]
+    fn test_any_of_separator() {
+        let mut tree = CompletionTree::default();
+        tree.set_min_word_len(1);
+        tree.separator(WordSeparator::AnyOf(vec![',', ';', ' ']));
+        tree.insert("a,b;c d");
+        assert_eq!(tree.word_count(), 4);
+        tree.insert("batman, robin;; batmobile");
+        assert_eq!(tree.word_count(), 7);
+        assert_eq!(
+            tree.complete("robin;bat"),
+            Some(vec![
+                "robin;batman".to_string(),
+                "robin;batmobile".to_string()
+            ])
+        );
+        let restored = CompletionTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert_eq!(restored.words(), tree.words());
+        assert_eq!(
+            restored.complete("x,rob"),
+            Some(vec!["x,robin".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_owned_separator() {
+        let sep = ["<", ">"].concat();
+        let mut tree = CompletionTree::default();
+        tree.separator(WordSeparator::Owned(sep));
+        tree.insert("batman<>robin<>batmobile");
+        assert_eq!(tree.word_count(), 3);
+        assert_eq!(
+            tree.complete("robin<>batm"),
+            Some(vec![
+                "robin<>batman".to_string(),
+                "robin<>batmobile".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_splitter() {
+        let mut tree = CompletionTree::default();
+        tree.set_min_word_len(3);
+        tree.set_splitter(|line| {
+            let mut words = vec![];
+            let mut start = 0;
+            for (i, c) in line.char_indices() {
+                if c.is_uppercase() || c == '_' {
+                    words.push(&line[start..i]);
+                    start = if c == '_' { i + 1 } else { i };
+                }
+            }
+            words.push(&line[start..]);
+            words
+        });
+        tree.insert("fireBoltSpell_ice_Frost");
+        assert_eq!(
+            tree.words(),
+            vec![
+                "Bolt".to_string(),
+                "Frost".to_string(),
+                "Spell".to_string(),
+                "fire".to_string(),
+                "ice".to_string(),
+            ]
+        );
+        assert_eq!(
+            tree.complete("cast fi"),
+            Some(vec!["cast fire".to_string()])
+        );
+        let cloned = tree.clone();
+        tree.clear();
+        tree.insert("batMobile");
+        assert!(tree.contains("Mobile"));
+        assert!(cloned.contains("Frost"));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex_separator() {
+        let mut tree = CompletionTree::default();
+        tree.set_min_word_len(3);
+        tree.separator(WordSeparator::Regex(
+            regex::Regex::new(r"[\s[:punct:]]+").unwrap(),
+        ));
+        tree.insert("hello, world!! foo");
+        assert_eq!(
+            tree.words(),
+            vec!["foo".to_string(), "hello".to_string(), "world".to_string()]
+        );
+        assert_eq!(
+            tree.complete("say,wor"),
+            Some(vec!["say,world".to_string()])
+        );
+        let restored = CompletionTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert_eq!(
+            restored.complete("say!!hel"),
+            Some(vec!["say!!hello".to_string()])
+        );
+    }
+
+    #[cfg(all(feature = "regex", feature = "serde"))]
+    #[test]
+    fn test_serde_regex_separator() {
+        let separator = WordSeparator::Regex(regex::Regex::new(r"[,;]").unwrap());
+        let json = serde_json::to_string(&separator).unwrap();
+        assert_eq!(json, r#"{"Regex":"[,;]"}"#);
+        let restored: WordSeparator = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, separator);
+        assert!(serde_json::from_str::<WordSeparator>(r#"{"Regex":"("}"#).is_err());
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.build_suffix_index();
+        tree.insert("Batman robin batmobile batcave robber running");
+        tree.remove("robber");
+        let words = tree.words();
+        let size = tree.size();
+        tree.shrink_to_fit();
+        assert_eq!(tree.words(), words);
+        assert_eq!(tree.size(), size);
+        assert_eq!(tree.complete("bat").unwrap().len(), 3);
+        assert_eq!(
+            tree.complete_suffix("ing"),
+            Some(vec!["running".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_memory_bytes() {
+        let mut tree = CompletionTree::default();
+        let mut last = tree.memory_bytes();
+        assert!(last >= std::mem::size_of::<CompletionTree>());
+        for word in ["batman", "batmobile", "batcave", "robin", "robber"] {
+            tree.insert(word);
+            let bytes = tree.memory_bytes();
+            assert!(bytes > last);
+            last = bytes;
+        }
+        tree.insert("batman");
+        assert_eq!(tree.memory_bytes(), last);
+        tree.build_suffix_index();
+        assert!(tree.memory_bytes() > last);
+    }
+
+    #[test]
+    fn test_subtree() {
+        fn render(node: &CompletionNode, partial: &mut String, out: &mut Vec<String>) {
+            if node.is_leaf() {
+                out.push(partial.clone());
+            }
+            for (c, child) in node.children() {
+                partial.push(c);
+                render(child, partial, out);
+                partial.pop();
+            }
+        }
+
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile batcave");
+        let node = tree.subtree("bat").unwrap();
+        assert_eq!(node.word_count(), 3);
+        assert_eq!(node.child('m').unwrap().word_count(), 2);
+        assert!(node.child('x').is_none());
+        let mut words = vec![];
+        render(node, &mut "bat".to_string(), &mut words);
+        assert_eq!(words, tree.complete("bat").unwrap());
+        assert_eq!(tree.subtree("").unwrap().word_count(), 4);
+        assert!(tree.subtree("joker").is_none());
+    }
+
+    #[test]
+    fn test_remove_prefix() {
+        let mut tree = CompletionTree::default();
+        tree.build_suffix_index();
+        tree.insert("batman robin batmobile");
+        let size = tree.size();
+        assert_eq!(tree.remove_prefix("joker"), 0);
+        assert_eq!(tree.remove_prefix("batmen"), 0);
+        assert_eq!(tree.size(), size);
+        assert_eq!(tree.remove_prefix("bat"), 2);
+        assert_eq!(tree.words(), vec!["robin".to_string()]);
+        assert_eq!(tree.size(), 6);
+        assert_eq!(tree.complete_suffix("man"), None);
+        assert_eq!(tree.complete_suffix("in"), Some(vec!["robin".to_string()]));
+        assert_eq!(tree.remove_prefix("robin"), 1);
+        assert_eq!(tree.size(), 1);
+        tree.insert("batman robin");
+        assert_eq!(tree.remove_prefix(""), 2);
+        assert_eq!(tree.word_count(), 0);
+    }
+
+    #[test]
+    fn test_case_insensitive_display() {
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.insert("batman");
+        tree.insert("Batman");
+        assert_eq!(tree.word_count(), 1);
+        assert_eq!(tree.complete("bat"), Some(vec!["Batman".to_string()]));
+        tree.insert("BATMAN robin");
+        assert_eq!(
+            tree.complete("to the bat"),
+            Some(vec!["to the BATMAN".to_string()])
+        );
+        assert_eq!(
+            tree.words(),
+            vec!["BATMAN".to_string(), "robin".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        let mut tree = CompletionTreeBuilder::new()
+            .inclusions(&['/', '_'])
+            .exclusions(&['0'])
+            .separator(WordSeparator::Separator("|"))
+            .min_word_len(3)
+            .max_word_len(10)
+            .min_completion_len(2)
+            .case_insensitive(true)
+            .strip_ansi(true)
+            .ignore_numeric(true)
+            .build();
+        assert_eq!(tree.min_word_len(), 3);
+        assert_eq!(tree.max_word_len(), Some(10));
+        assert_eq!(tree.min_completion_len(), 2);
+        assert!(tree.case_insensitive());
+        assert!(tree.strip_ansi());
+        assert!(tree.ignore_numeric());
+        tree.insert("/Bat_Man|123|port8080|\x1b[1mrob\x1b[0min|batmobile_extended");
+        assert_eq!(
+            tree.words(),
+            vec![
+                "/Bat_Man".to_string(),
+                "port8".to_string(),
+                "robin".to_string()
+            ]
+        );
+        assert_eq!(tree.complete("/"), None);
+        tree.set_min_word_len(1);
+        tree.insert("a");
+        assert!(tree.contains("A"));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin");
+        let snapshot = tree.snapshot();
+        let readers = (0..4)
+            .map(|_| {
+                let snapshot = snapshot.clone();
+                std::thread::spawn(move || snapshot.complete("bat"))
+            })
+            .collect::<Vec<_>>();
+        tree.insert("batmobile");
+        tree.remove("robin");
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), Some(vec!["batman".to_string()]));
+        }
+        assert!(snapshot.contains("robin"));
+        assert!(!snapshot.contains("batmobile"));
+        assert_eq!(tree.snapshot().word_count(), 2);
+    }
+
+    #[test]
+    fn test_complete_with_spans() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile");
+        assert_eq!(
+            tree.complete_with_spans("robin"),
+            Some(vec![("robin".to_string(), 0..5)])
+        );
+        assert_eq!(tree.complete_with_spans("joker"), None);
+
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.insert("ÉCLAIR éclairage");
+        let spans = tree.complete_with_spans("go écl").unwrap();
+        assert_eq!(
+            spans,
+            vec![
+                ("ÉCLAIR".to_string(), 0..4),
+                ("éclairage".to_string(), 0..4)
+            ]
+        );
+        for (word, span) in spans {
+            assert_eq!(word[span].to_lowercase(), "écl");
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_insert_all() {
+        let lines = [
+            "batman robin batmobile",
+            "Batcave \u{1b}[1mrobber\u{1b}[0m _joker 12345",
+            "robin harley_quinn ab",
+        ];
+        let mut serial = CompletionTree::with_inclusions(&['_']);
+        serial.set_case_insensitive(true);
+        serial.set_strip_ansi(true);
+        serial.set_ignore_numeric(true);
+        serial.build_suffix_index();
+        serial.set_track_insertion_order(true);
+        let mut parallel = serial.clone();
+        serial.insert("batarang");
+        parallel.insert("batarang");
+
+        lines.iter().for_each(|line| serial.insert(line));
+        parallel.par_insert_all(&lines);
+        assert_eq!(parallel.words(), serial.words());
+        assert_eq!(parallel.size(), serial.size());
+        assert_eq!(
+            parallel.complete_ranked("rob"),
+            serial.complete_ranked("rob")
+        );
+        assert_eq!(
+            parallel.complete_by_insertion_order("bat"),
+            serial.complete_by_insertion_order("bat")
+        );
+        assert_eq!(parallel.complete_suffix("er"), serial.complete_suffix("er"));
+    }
+
+    #[cfg(feature = "rustyline")]
+    #[test]
+    fn test_tree_completer() {
+        use crate::TreeCompleter;
+        use rustyline::completion::Completer;
+        use rustyline::history::MemHistory;
+        use rustyline::Context;
+
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile");
+        let mut completer = TreeCompleter::new(tree);
+        let history = MemHistory::new();
+        let ctx = Context::new(&history);
+        let replacements = |(start, pairs): (usize, Vec<rustyline::completion::Pair>)| {
+            (
+                start,
+                pairs.into_iter().map(|p| p.replacement).collect::<Vec<_>>(),
+            )
+        };
+
+        let line = "to the batm";
+        let result = completer.complete(line, line.len(), &ctx).unwrap();
+        assert_eq!(
+            replacements(result),
+            (7, vec!["batman".to_string(), "batmobile".to_string()])
+        );
+        let line = "call rob and alfred";
+        let result = completer.complete(line, 8, &ctx).unwrap();
+        assert_eq!(replacements(result), (5, vec!["robin".to_string()]));
+        let result = completer.complete(line, 9, &ctx).unwrap();
+        assert_eq!(replacements(result), (9, vec![]));
+        let result = completer.complete("joker", 5, &ctx).unwrap();
+        assert_eq!(replacements(result), (0, vec![]));
+
+        completer.tree_mut().insert("alfred");
+        let result = completer.complete(line, line.len(), &ctx).unwrap();
+        assert_eq!(replacements(result), (13, vec!["alfred".to_string()]));
+        assert_eq!(completer.tree().word_count(), 4);
+
+        completer.tree_mut().add_alias("btm", "batmobile");
+        let result = completer.complete("to the btm", 10, &ctx).unwrap();
+        assert_eq!(replacements(result), (7, vec!["batmobile".to_string()]));
+    }
+
+    #[test]
+    fn test_clear_and_reset() {
+        let mut tree = CompletionTree::with_inclusions(&['/']);
+        tree.separator(WordSeparator::Separator("|"));
+        tree.set_min_word_len(3);
+        tree.set_case_insensitive(true);
+        tree.build_suffix_index();
+        tree.insert("/Bat|robin");
+
+        tree.clear();
+        assert_eq!(tree.word_count(), 0);
+        assert_eq!(tree.min_word_len(), 3);
+        assert!(tree.case_insensitive());
+        tree.insert("/Joker|man");
+        assert_eq!(tree.words(), vec!["/Joker".to_string(), "man".to_string()]);
+        assert_eq!(tree.complete_suffix("er"), Some(vec!["/Joker".to_string()]));
+
+        tree.reset();
+        assert_eq!(tree.word_count(), 0);
+        assert_eq!(tree.min_word_len(), 5);
+        assert!(!tree.case_insensitive());
+        assert_eq!(tree.complete_suffix("er"), None);
+        tree.insert("Joker|harley");
+        assert_eq!(tree.words(), vec!["Joker".to_string()]);
+    }
+
+    #[test]
+    fn test_search_substring() {
+        let mut tree = CompletionTree::default();
+        tree.insert("acrobat batman robin batmobile");
+        assert_eq!(tree.search_substring("bat"), Vec::<String>::new());
+        tree.build_substring_index();
+        assert_eq!(
+            tree.search_substring("bat"),
+            vec![
+                "acrobat".to_string(),
+                "batman".to_string(),
+                "batmobile".to_string()
+            ]
+        );
+        assert_eq!(
+            tree.search_substring("ob"),
+            vec![
+                "acrobat".to_string(),
+                "batmobile".to_string(),
+                "robin".to_string()
+            ]
+        );
+        assert_eq!(tree.search_substring("joker"), Vec::<String>::new());
+        assert!(tree.remove("acrobat"));
+        assert_eq!(
+            tree.search_substring("ob"),
+            vec!["batmobile".to_string(), "robin".to_string()]
+        );
+        assert_eq!(tree.remove_prefix("bat"), 2);
+        assert_eq!(tree.search_substring("ob"), vec!["robin".to_string()]);
+
+        let restored = CompletionTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert_eq!(restored.search_substring("bi"), vec!["robin".to_string()]);
+        tree.clear();
+        assert_eq!(tree.search_substring("bi"), Vec::<String>::new());
+        tree.insert("cabin");
+        assert_eq!(tree.search_substring("bi"), vec!["cabin".to_string()]);
+    }
+
+    #[test]
+    fn test_search_substring_case_insensitive() {
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.build_substring_index();
+        tree.insert("AcroBat batman");
+        tree.insert("acrobat");
+        assert_eq!(
+            tree.search_substring("BAT"),
+            vec!["acrobat".to_string(), "batman".to_string()]
+        );
+        assert!(tree.remove("ACROBAT"));
+        assert_eq!(tree.search_substring("bat"), vec!["batman".to_string()]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_substring_index() {
+        let mut tree = CompletionTree::default();
+        tree.insert("acrobat batman");
+        let json = serde_json::to_string(&tree).unwrap();
+        let restored: CompletionTree = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.search_substring("bat"), Vec::<String>::new());
+
+        tree.build_substring_index();
+        let json = serde_json::to_string(&tree).unwrap();
+        let restored: CompletionTree = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            restored.search_substring("bat"),
+            tree.search_substring("bat")
+        );
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn test_fold_diacritics() {
+        let mut tree = CompletionTree::default();
+        tree.set_min_word_len(3);
+        tree.insert("caf\u{e9}");
+        assert_eq!(tree.complete("cafe"), None);
+
+        tree.set_fold_diacritics(true);
+        assert!(tree.fold_diacritics());
+        tree.insert("caf\u{e9} na\u{ef}ve mégère");
+        assert_eq!(tree.complete("cafe"), Some(vec!["caf\u{e9}".to_string()]));
+        assert_eq!(
+            tree.complete("caf\u{e9}"),
+            Some(vec!["caf\u{e9}".to_string()])
+        );
+        assert_eq!(tree.complete("nai"), Some(vec!["na\u{ef}ve".to_string()]));
+        assert_eq!(tree.complete("meg"), Some(vec!["mégère".to_string()]));
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn test_fold_diacritics_toggle() {
+        let mut tree = CompletionTree::default();
+        tree.insert("résumé");
+        tree.set_fold_diacritics(true);
+        assert_eq!(tree.complete("res"), Some(vec!["résumé".to_string()]));
+        assert!(tree.contains("résumé"));
+
+        tree.set_fold_diacritics(false);
+        assert_eq!(tree.complete("res"), None);
+        assert!(tree.remove("résumé"));
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_subword_indexing() {
+        let mut tree = CompletionTree::with_inclusions(&['_', '-']);
+        tree.set_min_word_len(3);
+        tree.insert("getUserName");
+        assert_eq!(tree.complete("user"), None);
+
+        tree.set_subword_indexing(true);
+        assert!(tree.subword_indexing());
+        tree.insert("getUserName parse_http-header HTTPServer lonely");
+        assert_eq!(tree.complete("user"), Some(vec!["user".to_string()]));
+        assert_eq!(
+            tree.words(),
+            vec![
+                "HTTPServer".to_string(),
+                "get".to_string(),
+                "getUserName".to_string(),
+                "header".to_string(),
+                "http".to_string(),
+                "lonely".to_string(),
+                "name".to_string(),
+                "parse".to_string(),
+                "parse_http-header".to_string(),
+                "server".to_string(),
+                "user".to_string(),
+            ]
+        );
+        assert_eq!(
+            tree.complete("get"),
+            Some(vec!["get".to_string(), "getUserName".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_subword_indexing_max_len() {
+        let mut tree = CompletionTree::default();
+        tree.set_max_word_len(8);
+        tree.set_subword_indexing(true);
+        let stats = tree.insert_counting("getUserNameFromDatabase setTimer");
+        assert_eq!(stats.inserted, 1);
+        assert_eq!(stats.skipped_long, 1);
+        assert!(!tree.contains("database"));
+        assert!(tree.contains("timer"));
+
+        #[cfg(feature = "rayon")]
+        {
+            let mut tree = CompletionTree::default();
+            tree.set_max_word_len(8);
+            tree.set_subword_indexing(true);
+            tree.par_insert_all(&["getUserNameFromDatabase setTimer"]);
+            assert!(!tree.contains("database"));
+            assert!(tree.contains("timer"));
+        }
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let mut tree = CompletionTree::with_inclusions(&['"']);
+        tree.set_min_word_len(2);
+        tree.insert("ab ac \"q");
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph completions {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("[label=\"b\"]"));
+        assert!(dot.contains("[label=\"c\"]"));
+        assert!(dot.contains("[label=\"ac\" shape=doublecircle]"));
+        assert!(dot.contains("[label=\"a\" shape=circle]"));
+        assert!(dot.contains("[label=\"\\\"q\" shape=doublecircle]"));
+        assert_eq!(dot.matches(" -> ").count(), tree.size() as usize - 1);
+    }
+
+    #[test]
+    fn test_iter_counts() {
+        let mut tree = CompletionTree::default();
+        assert_eq!(tree.iter_counts().count(), 0);
+        tree.set_case_insensitive(true);
+        tree.insert("Batman robin batmobile BATMAN robin batman");
+        assert_eq!(
+            tree.iter_counts().collect::<Vec<(String, u32)>>(),
+            vec![
+                ("batman".to_string(), 3),
+                ("batmobile".to_string(), 1),
+                ("robin".to_string(), 2),
+            ]
+        );
+        assert_eq!(tree.iter_counts().map(|(_, count)| count).sum::<u32>(), 6);
+    }
+
+    #[test]
+    fn test_try_complete() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile batcave robber");
+        assert_eq!(tree.try_complete("", 10), Err(CompleteError::NoMatch));
+        assert_eq!(tree.try_complete("joker", 10), Err(CompleteError::NoMatch));
+        assert_eq!(tree.try_complete("b", 2), Err(CompleteError::TooMany(3)));
+        assert_eq!(tree.try_complete("b", 3).ok(), tree.complete("b"));
+        assert_eq!(
+            tree.try_complete("to the rob", 1),
+            Err(CompleteError::TooMany(2))
+        );
+        assert_eq!(
+            tree.try_complete("to the robi", 1),
+            Ok(vec!["to the robin".to_string()])
+        );
+        assert_eq!(
+            CompleteError::TooMany(3).to_string(),
+            "too many matches (3)"
+        );
+    }
+
+    #[test]
+    fn test_complete_empty() {
+        let mut tree = CompletionTree::default();
+        tree.insert("robin batman");
+        assert_eq!(tree.complete(""), None);
+        assert_eq!(tree.complete("  "), None);
+
+        tree.set_complete_empty(true);
+        assert!(tree.complete_empty());
+        assert_eq!(
+            tree.complete(""),
+            Some(vec!["batman".to_string(), "robin".to_string()])
+        );
+        assert_eq!(
+            tree.complete("  "),
+            Some(vec!["  batman".to_string(), "  robin".to_string()])
+        );
+        assert_eq!(tree.count_completions(""), 2);
+        assert_eq!(tree.complete("rob"), Some(vec!["robin".to_string()]));
+    }
+
+    #[test]
+    fn test_complete_glob() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile batcave robber bitman");
+        assert_eq!(
+            tree.complete_glob("bat*"),
+            Some(vec![
+                "batcave".to_string(),
+                "batman".to_string(),
+                "batmobile".to_string()
+            ])
+        );
+        assert_eq!(
+            tree.complete_glob("ba?man"),
+            Some(vec!["batman".to_string()])
+        );
+        assert_eq!(
+            tree.complete_glob("b?tman"),
+            Some(vec!["batman".to_string(), "bitman".to_string()])
+        );
+        assert_eq!(
+            tree.complete_glob("*bile"),
+            Some(vec!["batmobile".to_string()])
+        );
+        assert_eq!(tree.complete_glob("*b*r"), Some(vec!["robber".to_string()]));
+        assert_eq!(tree.complete_glob("**"), Some(tree.words()));
+        assert_eq!(tree.complete_glob("rob"), tree.complete("rob"));
+        assert_eq!(tree.complete_glob("j*"), None);
+        assert_eq!(tree.complete_glob("*joker"), None);
+
+        let word = "ab".repeat(20);
+        tree.insert(&word);
+        assert_eq!(
+            tree.complete_glob(&"*a".repeat(12)),
+            Some(vec![word.clone()])
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let mut tree = CompletionTree::default();
+        assert_eq!(format!("{}", tree), "");
+        tree.insert("robin");
+        assert_eq!(format!("{}", tree), "robin");
+        tree.set_case_insensitive(true);
+        tree.insert("Batman batmobile");
+        assert_eq!(format!("{}", tree), "Batman\nbatmobile\nrobin");
+    }
+
+    #[test]
+    fn test_into_iterator() {
+        let mut tree = CompletionTree::default();
+        assert_eq!((&tree).into_iter().next(), None);
+        tree.set_case_insensitive(true);
+        tree.insert("robin Batman batmobile");
+        let mut words = vec![];
+        for word in &tree {
+            words.push(word);
+        }
+        assert_eq!(words, tree.words());
+        assert_eq!(tree.into_iter().count(), tree.word_count() as usize);
+    }
+
+    #[test]
+    fn test_partial_eq() {
+        let mut split = CompletionTree::default();
+        split.set_min_word_len(1);
+        split.insert("a b c");
+        let mut separate = CompletionTree::default();
+        separate.set_min_word_len(1);
+        separate.insert("c");
+        separate.insert("b");
+        separate.insert("a");
+        separate.insert("a");
+        assert_eq!(split, separate);
+        assert_eq!(CompletionTree::default(), CompletionTree::default());
+
+        let mut folded = CompletionTree::default();
+        folded.set_min_word_len(1);
+        folded.set_case_insensitive(true);
+        folded.insert("A b c");
+        assert_ne!(split, folded);
+        folded.insert("a");
+        assert_eq!(split, folded);
+        assert!(separate.remove("b"));
+        assert_ne!(split, separate);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile batcave robber");
+        let size = tree.size();
+        assert_eq!(tree.retain(|word| word.chars().count() > 6), 3);
+        assert_eq!(
+            tree.words(),
+            vec!["batcave".to_string(), "batmobile".to_string()]
+        );
+        assert!(tree.size() < size);
+        assert_eq!(tree.size(), 14);
+        assert_eq!(tree.retain(|_| true), 0);
+
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.insert("Batman batmobile");
+        assert_eq!(tree.retain(|word| word != "Batman"), 1);
+        assert_eq!(tree.words(), vec!["batmobile".to_string()]);
+    }
+
+    #[test]
+    fn test_complete_filtered() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile batcave robber");
+        assert_eq!(
+            tree.complete_filtered("bat", |_| true),
+            tree.complete("bat")
+        );
+        assert_eq!(tree.complete_filtered("bat", |_| false), None);
+        assert_eq!(tree.complete_filtered("joker", |_| true), None);
+
+        let mut seen = vec![];
+        assert_eq!(
+            tree.complete_filtered("to the rob", |word| {
+                seen.push(word.to_string());
+                word != "robin"
+            }),
+            Some(vec!["to the robber".to_string()])
+        );
+        seen.sort();
+        assert_eq!(seen, vec!["robber".to_string(), "robin".to_string()]);
+    }
+
+    #[test]
+    fn test_max_results() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile batcave robber");
+        assert_eq!(tree.max_results(), None);
+        tree.set_max_results(Some(3));
+        assert_eq!(tree.complete("bat").map(|c| c.len()), Some(3));
+        tree.set_max_results(Some(2));
+        assert_eq!(tree.complete("bat"), None);
+        assert_eq!(tree.complete("to the batm").map(|c| c.len()), Some(2));
+        assert_eq!(tree.try_complete("bat", 10), Err(CompleteError::TooMany(3)));
+        tree.set_max_results(Some(0));
+        assert_eq!(tree.complete("rob"), None);
+        tree.set_max_results(None);
+        assert_eq!(tree.complete("rob").map(|c| c.len()), Some(2));
+
+        let restored = CompletionTree::from_bytes(
+            &CompletionTreeBuilder::new()
+                .max_results(Some(4))
+                .build()
+                .to_bytes(),
+        )
+        .unwrap();
+        assert_eq!(restored.max_results(), Some(4));
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    #[test]
+    fn test_graphemes() {
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        let mut tree = CompletionTree::with_inclusions(&['\u{1f468}', '\u{1f469}', '\u{1f467}']);
+        tree.set_min_word_len(1);
+        tree.insert(format!("{}hello", family));
+        assert_eq!(tree.words(), vec!["\u{1f468}".to_string()]);
+
+        let mut tree = CompletionTree::with_inclusions(&['\u{1f468}', '\u{1f469}', '\u{1f467}']);
+        tree.set_min_word_len(1);
+        tree.set_graphemes(true);
+        assert!(tree.graphemes());
+        tree.insert(format!("{}hello robin", family));
+        assert_eq!(tree.words(), vec!["robin".to_string()]);
+
+        tree.add_inclusion('\u{200d}');
+        tree.add_inclusion('\u{1f466}');
+        let brother = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f466}";
+        tree.insert(format!("{}hello {}world", family, brother));
+        assert_eq!(
+            tree.complete("\u{1f468}"),
+            Some(vec![
+                format!("{}world", brother),
+                format!("{}hello", family)
+            ])
+        );
+        assert_eq!(
+            tree.longest_common_prefix("\u{1f468}"),
+            Some("\u{1f468}".to_string())
+        );
+        assert_eq!(
+            tree.longest_common_prefix(family),
+            Some(format!("{}hello", family))
+        );
+    }
+
+    #[test]
+    fn test_complete_extensions() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile");
+        assert_eq!(
+            tree.complete_extensions("bat"),
+            Some(vec!["man".to_string(), "mobile".to_string()])
+        );
+        assert_eq!(
+            tree.complete_extensions("robin"),
+            Some(vec!["".to_string()])
+        );
+        assert_eq!(tree.complete_extensions("joker"), None);
+
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.insert("BatMan");
+        assert_eq!(
+            tree.complete_extensions("to the bat"),
+            Some(vec!["Man".to_string()])
+        );
+
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile batcave");
+        tree.add_alias("bt", "bolt");
+        assert_eq!(
+            tree.complete_extensions("b"),
+            Some(vec![
+                "olt".to_string(),
+                "atcave".to_string(),
+                "atman".to_string(),
+                "atmobile".to_string()
+            ])
+        );
+        tree.set_max_results(Some(2));
+        assert_eq!(tree.complete_extensions("bat"), None);
+        assert_eq!(tree.complete("bat"), None);
+    }
+
+    #[test]
+    fn test_dedup_folded_completions() {
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.insert("Batman batman");
+        assert_eq!(tree.complete("bat"), Some(vec!["batman".to_string()]));
+
+        let mut tree = CompletionTree::default();
+        tree.insert("baTman");
+        tree.set_case_insensitive(true);
+        tree.insert("baTman batcave");
+        assert_eq!(tree.words().len(), 2);
+        assert_eq!(
+            tree.complete("ba"),
+            Some(vec!["baTman".to_string(), "batcave".to_string()])
+        );
+        assert_eq!(
+            tree.complete_ranked("ba"),
+            Some(vec!["baTman".to_string(), "batcave".to_string()])
+        );
+        assert_eq!(
+            tree.complete_by_insertion_order("ba"),
+            Some(vec!["batcave".to_string(), "baTman".to_string()])
+        );
+        assert_eq!(
+            tree.top_k("ba", 2),
+            Some(vec!["baTman".to_string(), "batcave".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_depth_histogram() {
+        let mut tree = CompletionTree::default();
+        assert_eq!(tree.depth_histogram(), vec![1]);
+        tree.insert("batman robin batmobile batcave robber");
+        let histogram = tree.depth_histogram();
+        assert_eq!(histogram, vec![1, 2, 2, 2, 4, 5, 4, 2, 1, 1]);
+        assert_eq!(histogram.iter().sum::<u32>(), tree.size());
+    }
+
+    #[test]
+    fn test_first_completion() {
+        let mut tree = CompletionTree::default();
+        assert_eq!(tree.first_completion("bat"), None);
+        tree.insert("batman robin batmobile batcave robber");
+        for line in &["b", "bat", "batm", "to the rob", "robin"] {
+            assert_eq!(
+                tree.first_completion(line),
+                tree.complete(line).map(|c| c[0].clone())
+            );
+        }
+        assert_eq!(tree.first_completion("jok"), None);
+    }
+
+    #[test]
+    fn test_insert_with_count() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman batman robin batmobile");
+        let counts = tree.iter_counts().collect::<Vec<(String, u32)>>();
+
+        let mut restored = CompletionTree::default();
+        for (word, count) in &counts {
+            restored.insert_with_count(word, *count);
+        }
+        assert_eq!(restored.iter_counts().collect::<Vec<_>>(), counts);
+        assert_eq!(
+            restored.complete_ranked("bat"),
+            Some(vec!["batman".to_string(), "batmobile".to_string()])
+        );
+
+        restored.insert_with_count("bat", 5);
+        restored.insert_with_count("joker", 0);
+        restored.insert_with_count("batman robin", 2);
+        assert_eq!(restored.words(), tree.words());
+    }
+
+    #[test]
+    fn test_trailing_separator() {
+        let mut tree = CompletionTree::default();
+        tree.set_min_word_len(3);
+        tree.insert("the theme batman");
+        assert_eq!(
+            tree.complete("to the"),
+            Some(vec!["to the".to_string(), "to theme".to_string()])
+        );
+        assert_eq!(tree.complete("to the "), None);
+        assert_eq!(tree.complete("to the\t"), None);
+        assert!(!tree.has_completions("to the "));
+        tree.set_complete_empty(true);
+        assert_eq!(tree.count_completions("to the "), 3);
+        assert_eq!(
+            tree.complete("to the "),
+            Some(vec![
+                "to the batman".to_string(),
+                "to the the".to_string(),
+                "to the theme".to_string()
+            ])
+        );
+
+        let mut tree = CompletionTree::default();
+        tree.set_min_word_len(3);
+        tree.separator(WordSeparator::Separator(","));
+        tree.insert("the,theme,batman");
+        assert_eq!(tree.complete("to,the,"), None);
+        assert_eq!(
+            tree.complete("to,the"),
+            Some(vec!["to,the".to_string(), "to,theme".to_string()])
+        );
+        tree.set_complete_empty(true);
+        assert_eq!(tree.count_completions("to,the,"), 3);
+    }
+
+    #[test]
+    fn test_insert_counting() {
+        let mut tree = CompletionTree::default();
+        assert_eq!(tree.insert_counting(""), InsertStats::default());
+        tree.set_max_word_len(7);
+        tree.set_ignore_numeric(true);
+        let stats = tree.insert_counting("to the batmobile with robin and 123456");
+        assert_eq!(
+            stats,
+            InsertStats {
+                inserted: 1,
+                skipped_short: 4,
+                skipped_long: 1,
+                skipped_other: 1,
+            }
+        );
+        assert_eq!(tree.word_count() as usize, stats.inserted);
+    }
+
+    #[test]
+    fn test_complete_at() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile");
+        let line = "to the bat and rob";
+        assert_eq!(
+            tree.complete_at(line, 10),
+            Some((vec!["batman".to_string(), "batmobile".to_string()], 7..10))
+        );
+        assert_eq!(
+            tree.complete_at(line, 16),
+            Some((vec!["robin".to_string()], 15..18))
+        );
+        assert_eq!(
+            tree.complete_at(line, line.len()),
+            tree.complete_words(line).map(|words| (words, 15..18))
+        );
+        assert_eq!(
+            tree.complete_at("batxyz", 3),
+            Some((vec!["batman".to_string(), "batmobile".to_string()], 0..6))
+        );
+        assert_eq!(tree.complete_at(line, 7), None);
+        assert_eq!(tree.complete_at(line, 100), None);
+        assert_eq!(tree.complete_at("\u{e9}bat", 1), None);
+
+        let mut tree = CompletionTree::default();
+        tree.separator(WordSeparator::Separator(","));
+        tree.insert("batman,robin");
+        assert_eq!(
+            tree.complete_at("rob,batm,x", 6),
+            Some((vec!["batman".to_string()], 4..8))
+        );
+    }
+
+    #[test]
+    fn test_insert_exact() {
+        let mut tree = CompletionTree::default();
+        assert_eq!(tree.min_word_len(), 5);
+        tree.set_max_word_len(8);
+        tree.set_ignore_numeric(true);
+        tree.insert("go ls 42 supercalifragilistic");
+        assert_eq!(tree.word_count(), 0);
+        tree.insert_exact("go");
+        tree.insert_exact("42");
+        tree.insert_exact("supercalifragilistic");
+        assert_eq!(tree.complete("g"), Some(vec!["go".to_string()]));
+        assert_eq!(tree.complete("4"), Some(vec!["42".to_string()]));
+        assert_eq!(tree.word_count(), 3);
+        tree.insert_exact("go home");
+        assert_eq!(tree.complete("g"), Some(vec!["go".to_string()]));
+    }
+
+    #[test]
+    fn test_cached_word_count() {
+        let mut tree = CompletionTree::default();
+        assert!(tree.is_empty());
+        let recount = |tree: &CompletionTree| tree.words().len() as u32;
+        tree.insert("batman robin batmobile batcave robber batman");
+        assert_eq!(tree.word_count(), recount(&tree));
+        assert_eq!(tree.word_count(), 5);
+        assert!(tree.remove("robin"));
+        assert!(!tree.remove("robin"));
+        assert!(!tree.remove("bat"));
+        assert_eq!(tree.word_count(), recount(&tree));
+        tree.insert_exact("go");
+        tree.insert_with_count("joker", 3);
+        assert_eq!(tree.word_count(), recount(&tree));
+        assert_eq!(tree.remove_prefix("batm"), 2);
+        assert_eq!(tree.word_count(), recount(&tree));
+        tree.prune_below(2);
+        assert_eq!(tree.word_count(), recount(&tree));
+        assert_eq!(tree.word_count(), 1);
+        tree.insert("batman robin");
+        tree.retain(|word| word != "robin");
+        assert_eq!(tree.word_count(), recount(&tree));
+        let restored = CompletionTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert_eq!(restored.word_count(), tree.word_count());
+        tree.clear();
+        assert!(tree.is_empty());
+        tree.insert("batman");
+        tree.reset();
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_complete_each() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile batcave robber");
+        let mut found = vec![];
+        assert!(tree.complete_each("to the b", |c| found.push(c.to_string())));
+        assert_eq!(Some(found), tree.complete("to the b"));
+        assert!(!tree.complete_each("joker", |_| panic!("no completions expected")));
+        assert!(!tree.complete_each("", |_| panic!("no completions expected")));
+
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.insert("Batman");
+        let mut found = vec![];
+        assert!(tree.complete_each("to the BAT", |c| found.push(c.to_string())));
+        assert_eq!(found, vec!["to the Batman".to_string()]);
+    }
+
+    #[test]
+    fn test_difference_intersection() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile batcave robber");
+        let mut other = CompletionTree::default();
+        other.insert("robin joker batcave");
+
+        let difference = tree.difference(&other);
+        assert_eq!(
+            difference.words(),
+            vec![
+                "batman".to_string(),
+                "batmobile".to_string(),
+                "robber".to_string()
+            ]
+        );
+        assert_eq!(difference.complete("batc"), None);
+        let intersection = tree.intersection(&other);
+        assert_eq!(
+            intersection.words(),
+            vec!["batcave".to_string(), "robin".to_string()]
+        );
+        assert_eq!(intersection.word_count(), 2);
+        assert_eq!(tree.word_count(), 5);
+        assert_eq!(
+            tree.intersection(&CompletionTree::default()).word_count(),
+            0
+        );
+        assert_eq!(tree.difference(&CompletionTree::default()), tree);
+    }
+
+    #[test]
+    fn test_complete_all_tokens() {
+        let mut tree = CompletionTree::default();
+        tree.insert("alpha alpine betamax");
+        tree.separator(WordSeparator::Separator(","));
+        assert_eq!(
+            tree.complete_all_tokens("al,,be,gamma,alph"),
+            vec![
+                (0..2, vec!["alpha".to_string(), "alpine".to_string()]),
+                (4..6, vec!["betamax".to_string()]),
+                (13..17, vec!["alpha".to_string()]),
+            ]
+        );
+        tree.set_min_completion_len(3);
+        assert_eq!(
+            tree.complete_all_tokens("al,bet"),
+            vec![(3..6, vec!["betamax".to_string()])]
+        );
+        assert!(tree.complete_all_tokens("").is_empty());
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let mut tree = CompletionTree::with_capacity(10);
+        assert!(tree.is_empty());
+        tree.insert("alpha alpine");
+        assert_eq!(tree, {
+            let mut other = CompletionTree::default();
+            other.insert("alpine alpha");
+            other
+        });
+    }
+
+    #[test]
+    fn test_next_chars() {
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.insert("Status Stash Start");
+        assert_eq!(tree.next_chars(""), Some(vec!['s']));
+        assert_eq!(tree.next_chars("STA"), Some(vec!['r', 's', 't']));
+        assert_eq!(tree.next_chars("start"), Some(vec![]));
+        assert_eq!(tree.next_chars("stop"), None);
+    }
+
+    #[test]
+    fn test_truncate_on_invalid() {
+        let mut tree = CompletionTree::default();
+        tree.set_min_word_len(2);
+        tree.insert("ab#cd");
+        assert_eq!(tree.words(), vec!["ab".to_string()]);
+
+        let mut tree = CompletionTreeBuilder::default()
+            .min_word_len(2)
+            .truncate_on_invalid(false)
+            .build();
+        let stats = tree.insert_counting("ab#cd efgh");
+        assert_eq!(stats.skipped_other, 1);
+        assert_eq!(tree.words(), vec!["efgh".to_string()]);
+        assert!(!CompletionTree::from_bytes(&tree.to_bytes())
+            .unwrap()
+            .truncate_on_invalid());
+    }
+
+    #[test]
+    fn test_longest_match() {
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.set_min_word_len(2);
+        tree.insert("New NewYork");
+        assert_eq!(tree.longest_match("NEWYORKER"), Some("NewYork".to_string()));
+        assert_eq!(tree.longest_match("newt"), Some("New".to_string()));
+        assert_eq!(tree.longest_match("ne"), None);
+        assert_eq!(tree.longest_match(""), None);
+    }
+
+    #[test]
+    fn test_with_inclusions_ci() {
+        let mut tree = CompletionTree::with_inclusions_ci(&['X', '@']);
+        tree.set_exclusions(&['x', 'X']);
+        tree.insert("maxed @MAXED");
+        assert_eq!(tree.complete("max"), Some(vec!["maxed".to_string()]));
+        assert_eq!(tree.complete("@MAX"), Some(vec!["@MAXED".to_string()]));
+
+        let mut tree = CompletionTree::with_inclusions(&['X']);
+        tree.set_exclusions(&['x', 'X']);
+        tree.insert("maxed");
+        assert_eq!(tree.complete("max"), None);
+    }
+
+    #[test]
+    fn test_decay() {
+        let mut tree = CompletionTree::default();
+        tree.insert_with_count("alpha", 100);
+        tree.insert_with_count("alpine", 7);
+        tree.insert("alpaca");
+        tree.decay(0.5);
+        tree.decay(0.5);
+        assert_eq!(
+            tree.iter_counts().collect::<Vec<(String, u32)>>(),
+            vec![
+                ("alpaca".to_string(), 0),
+                ("alpha".to_string(), 25),
+                ("alpine".to_string(), 1),
+            ]
+        );
+        assert_eq!(tree.word_count(), 3);
+        tree.decay(2.0);
+        assert_eq!(tree.iter_counts().nth(1), Some(("alpha".to_string(), 25)));
+        tree.decay(f32::NAN);
+        assert_eq!(tree.prune_below(1), 3);
+        assert!(tree.is_empty());
+
+        tree.insert_with_count("alpha", u32::MAX);
+        tree.decay(1.0);
+        assert_eq!(
+            tree.iter_counts().next(),
+            Some(("alpha".to_string(), u32::MAX))
+        );
+        tree.insert_with_count("alpine", 3);
+        tree.insert("alpaca");
+        assert_eq!(tree.decay_and_prune(0.5), 1);
+        assert_eq!(
+            tree.iter_counts().collect::<Vec<(String, u32)>>(),
+            vec![
+                ("alpha".to_string(), u32::MAX / 2),
+                ("alpine".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_try_with_inclusions() {
+        let mut tree = CompletionTree::try_with_inclusions(&['-']).unwrap();
+        tree.insert("test-hyphen");
+        assert_eq!(tree.complete("te"), Some(vec!["test-hyphen".to_string()]));
+        assert_eq!(
+            CompletionTree::try_with_inclusions(&['\t', '7']).err(),
+            Some(InclusionError::Whitespace('\t'))
+        );
+        assert_eq!(
+            CompletionTree::try_with_inclusions(&['-', '7']).err(),
+            Some(InclusionError::Alphanumeric('7'))
+        );
+        assert_eq!(
+            InclusionError::Alphanumeric('7').to_string(),
+            "'7' is already accepted"
+        );
+    }
+
+    #[test]
+    fn test_infix_indexing() {
+        let mut tree = CompletionTree::with_inclusions(&['_', '-']);
+        tree.set_min_word_len(2);
+        tree.insert("db_connection_pool HTTPConnection");
+        assert!(tree.search_infix("conn").is_empty());
+        tree.set_infix_indexing(true);
+        tree.insert("reconnect");
+        assert_eq!(
+            tree.search_infix("Conn"),
+            vec![
+                "HTTPConnection".to_string(),
+                "db_connection_pool".to_string()
+            ]
+        );
+        assert_eq!(
+            tree.search_infix("http"),
+            vec!["HTTPConnection".to_string()]
+        );
+        assert_eq!(
+            tree.search_infix("connection_p"),
+            vec!["db_connection_pool".to_string()]
+        );
+        assert_eq!(tree.search_infix("rec"), vec!["reconnect".to_string()]);
+        assert!(tree.remove("HTTPConnection"));
+        assert_eq!(
+            tree.search_infix("conn"),
+            vec!["db_connection_pool".to_string()]
+        );
+        let restored = CompletionTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert_eq!(restored.search_infix("pool"), tree.search_infix("pool"));
+        tree.set_infix_indexing(false);
+        assert!(tree.search_infix("pool").is_empty());
+    }
+
+    #[test]
+    fn test_complete_ignore_case() {
+        let mut tree = CompletionTree::default();
+        tree.insert("Batman BATMOBILE battery robin");
+        assert_eq!(
+            tree.complete_ignore_case("bAtM"),
+            Some(vec!["BATMOBILE".to_string(), "Batman".to_string()])
+        );
+        assert_eq!(
+            tree.complete_ignore_case("bat"),
+            Some(vec![
+                "BATMOBILE".to_string(),
+                "Batman".to_string(),
+                "battery".to_string()
+            ])
+        );
+        assert_eq!(tree.complete("bat"), Some(vec!["battery".to_string()]));
+        assert_eq!(tree.complete_ignore_case("joker"), None);
+        assert_eq!(tree.complete_ignore_case(""), None);
+    }
+
+    #[test]
+    fn test_word_count_with_prefix() {
+        let mut tree = CompletionTree::default();
+        tree.set_min_word_len(3);
+        tree.set_min_completion_len(4);
+        tree.insert("bat batman batmobile robin");
+        assert_eq!(tree.word_count_with_prefix("bat"), 3);
+        assert_eq!(tree.count_completions("bat"), 0);
+        assert_eq!(tree.word_count_with_prefix("batman"), 1);
+        assert_eq!(tree.word_count_with_prefix("batmen"), 0);
+        assert_eq!(tree.word_count_with_prefix("to the bat"), 0);
+        assert_eq!(tree.word_count_with_prefix(""), tree.word_count());
+    }
+
+    #[test]
+    fn test_strict_charset() {
+        let hex = "0123456789abcdefABCDEF".chars().collect::<Vec<char>>();
+        let mut tree = CompletionTree::with_inclusions(&hex);
+        tree.set_min_word_len(2);
+        tree.set_strict_charset(true);
+        tree.insert("deadBEEF cafeXbabe");
+        assert_eq!(
+            tree.words(),
+            vec!["cafe".to_string(), "deadBEEF".to_string()]
+        );
+        let mut restored = CompletionTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert!(restored.strict_charset());
+        restored.insert("f00dZ");
+        assert!(restored.contains("f00d"));
+
+        tree.set_strict_charset(false);
+        tree.insert("cafeXbabe");
+        assert!(tree.contains("cafeXbabe"));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut tree = CompletionTreeBuilder::default().capacity(Some(3)).build();
+        tree.insert("alpha bravo charlie");
+        tree.insert("alpha");
+        tree.insert("delta");
+        assert_eq!(tree.word_count(), 3);
+        assert!(!tree.contains("bravo"));
+        assert_eq!(tree.complete("char"), Some(vec!["charlie".to_string()]));
+        tree.insert("echo1");
+        assert_eq!(
+            tree.words(),
+            vec![
+                "charlie".to_string(),
+                "delta".to_string(),
+                "echo1".to_string()
+            ]
+        );
+
+        let mut restored = CompletionTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert_eq!(restored.capacity(), Some(3));
+        restored.insert("foxtrot");
+        assert!(!restored.contains("charlie"));
+
+        tree.set_capacity(Some(1));
+        assert_eq!(tree.words(), vec!["echo1".to_string()]);
+        tree.set_capacity(None);
+        tree.insert("golfer hotel");
+        assert_eq!(tree.word_count(), 3);
+    }
+
+    #[test]
+    fn test_complete_detailed() {
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.insert("Gotham gothamite");
+        assert_eq!(
+            tree.complete_detailed("to GOTHAM"),
+            Some(CompleteResult {
+                exact: true,
+                completions: vec!["to Gotham".to_string(), "to gothamite".to_string()],
+            })
+        );
+        assert_eq!(
+            tree.complete_detailed("gothamit"),
+            Some(CompleteResult {
+                exact: false,
+                completions: vec!["gothamite".to_string()],
+            })
+        );
+        assert_eq!(tree.complete_detailed("metropolis"), None);
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.insert("batmobile robin");
+        tree.add_alias("BTM", "batmobile");
+        tree.add_alias("btm", "Batcave");
+        tree.add_alias("rb", "robin");
+        assert_eq!(
+            tree.complete("bt"),
+            Some(vec!["Batcave".to_string(), "batmobile".to_string()])
+        );
+        assert_eq!(tree.complete("RB"), Some(vec!["robin".to_string()]));
+        assert_eq!(tree.complete("btmx"), None);
+        assert_eq!(tree.count_completions("bt"), 2);
+        assert_eq!(tree.count_completions("rb"), 1);
+        assert_eq!(tree.count_completions("r"), 1);
+        assert_eq!(
+            tree.try_complete("to the bt", 2),
+            Ok(vec![
+                "to the Batcave".to_string(),
+                "to the batmobile".to_string()
+            ])
+        );
+        assert_eq!(tree.try_complete("bt", 1), Err(CompleteError::TooMany(2)));
+        assert_eq!(
+            tree.complete_detailed("bt"),
+            Some(CompleteResult {
+                exact: false,
+                completions: vec!["Batcave".to_string(), "batmobile".to_string()],
+            })
+        );
+        tree.clear();
+        assert_eq!(tree.word_count(), 0);
+        let restored = CompletionTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert_eq!(restored.complete("r"), Some(vec!["robin".to_string()]));
+        assert!(tree.remove_alias("Btm"));
+        assert!(!tree.remove_alias("btm"));
+        assert_eq!(tree.complete("bt"), None);
+    }
+
+    #[test]
+    fn test_complete_at_multibyte_cursor() {
+        let mut tree = CompletionTree::default();
+        tree.insert("caf\u{e9}ine caf\u{e9}s");
+        let line = "un caf\u{e9} noir";
+        assert_eq!(
+            tree.complete_at(line, 8),
+            Some((
+                vec!["caf\u{e9}ine".to_string(), "caf\u{e9}s".to_string()],
+                3..8
+            ))
+        );
+        for cursor in 0..=line.len() + 1 {
+            if !line.is_char_boundary(cursor) {
+                assert_eq!(tree.complete_at(line, cursor), None);
+            }
+        }
+        assert_eq!(tree.complete_at(line, 7), None);
+        assert_eq!(tree.complete_at(line, line.len() + 1), None);
+    }
+
+    #[test]
+    fn test_suggest_corrections() {
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.insert("Batman batmen catman robin robins");
+        assert_eq!(
+            tree.suggest_corrections("batmn", 1),
+            vec!["Batman".to_string(), "batmen".to_string()]
+        );
+        assert_eq!(
+            tree.suggest_corrections("BATMAN", 1),
+            vec![
+                "Batman".to_string(),
+                "batmen".to_string(),
+                "catman".to_string()
+            ]
+        );
+        assert_eq!(
+            tree.suggest_corrections("robn", 2),
+            vec!["robin".to_string(), "robins".to_string()]
+        );
+        assert!(tree.suggest_corrections("joker", 1).is_empty());
+    }
+
+    #[test]
+    fn test_complete_owned_query() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman batmobile");
+        let query = String::from("to the batmo");
+        assert_eq!(
+            tree.complete(&query),
+            Some(vec!["to the batmobile".to_string()])
+        );
+        assert_eq!(
+            tree.complete(query),
+            Some(vec!["to the batmobile".to_string()])
+        );
+        assert_eq!(
+            tree.complete(std::borrow::Cow::Borrowed("batm")),
+            tree.complete("batm")
+        );
+    }
+
+    #[test]
+    fn test_string_arguments() {
+        let mut tree = CompletionTree::default();
+        let owned = String::from("batman");
+        tree.insert(&owned);
+        tree.insert(String::from("batmobile"));
+        tree.insert(std::borrow::Cow::Borrowed("batcave"));
+        assert_eq!(tree.word_count(), 3);
+        assert!(tree.contains(&owned));
+        assert!(tree.contains(String::from("batcave")));
+        assert!(!tree.contains(std::borrow::Cow::from("batm")));
+    }
+
+    #[test]
+    fn test_prefix_node_exists() {
+        let mut tree = CompletionTree::default();
+        tree.set_min_completion_len(5);
+        tree.insert("batman batmobile");
+        assert!(tree.prefix_node_exists("batm"));
+        assert!(!tree.has_completions("batm"));
+        assert!(!tree.prefix_node_exists("batx"));
+        assert!(!tree.has_completions("batx"));
+        assert!(tree.has_completions("the batmo"));
+        assert!(!tree.prefix_node_exists("the batmo"));
+        assert!(tree.prefix_node_exists(""));
+    }
+
+    #[test]
+    fn test_sort_order() {
+        let mut tree = CompletionTreeBuilder::default()
+            .sort_order(SortOrder::ShortestFirst)
+            .build();
+        tree.insert("batmobile batman batcave batarang");
+        assert_eq!(
+            tree.complete("bat"),
+            Some(vec![
+                "batman".to_string(),
+                "batcave".to_string(),
+                "batarang".to_string(),
+                "batmobile".to_string(),
+            ])
+        );
+        tree.set_sort_order(SortOrder::AlphabeticalReverse);
+        assert_eq!(
+            tree.complete("to the batm"),
+            Some(vec![
+                "to the batmobile".to_string(),
+                "to the batman".to_string()
+            ])
+        );
+        let reversed = Some(vec!["batmobile".to_string(), "batman".to_string()]);
+        assert_eq!(tree.complete_words("batm"), reversed);
+        assert_eq!(tree.complete_max_depth("batm", 5), reversed);
+        assert_eq!(tree.complete_filtered("batm", |_| true), reversed);
+        assert_eq!(tree.complete_phrase("batm", 1), reversed);
+        assert_eq!(
+            tree.complete_with_spans("batm"),
+            Some(vec![
+                ("batmobile".to_string(), 0..4),
+                ("batman".to_string(), 0..4)
+            ])
+        );
+        let restored = CompletionTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert_eq!(restored.sort_order(), SortOrder::AlphabeticalReverse);
+        assert_eq!(CompletionTree::default().sort_order(), SortOrder::default());
+    }
+
+    #[test]
+    fn test_top_k_large_k() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman robin batmobile batman");
+        assert_eq!(
+            tree.top_k("bat", usize::MAX),
+            Some(vec!["batman".to_string(), "batmobile".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_max_results_unbounded_cap() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman batmobile");
+        tree.set_max_results(Some(usize::MAX));
+        assert_eq!(
+            tree.complete("bat"),
+            Some(vec!["batman".to_string(), "batmobile".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_remove_unaccepted_char() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman");
+        assert!(!tree.contains("batman#x"));
+        assert!(!tree.remove("batman#x"));
+        assert!(!tree.remove("batman#"));
+        assert!(tree.contains("batman"));
+        assert_eq!(tree.word_count(), 1);
+    }
+
+    #[test]
+    fn test_top_k_zero() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman batmobile");
+        assert_eq!(tree.top_k("bat", 0), None);
+        assert_eq!(tree.top_k("bat", 1), Some(vec!["batman".to_string()]));
+    }
+
+    #[test]
+    fn test_completion_map_trailing_whitespace() {
+        let mut map = CompletionMap::default();
+        map.insert("help", 1);
+        map.insert("helpers", 2);
+        assert_eq!(map.complete("help "), None);
+        assert_eq!(map.complete("say help\t"), None);
+        assert_eq!(
+            map.complete("say help"),
+            Some(vec![
+                ("say help".to_string(), &1),
+                ("say helpers".to_string(), &2)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_no_empty_words() {
+        let mut tree = CompletionTree::default();
+        tree.set_complete_empty(true);
+        tree.insert("#foobar batman");
+        assert_eq!(tree.word_count(), 1);
+        assert_eq!(tree.words(), vec!["batman".to_string()]);
+        assert_eq!(tree.complete(""), Some(vec!["batman".to_string()]));
+        tree.insert_exact("#");
+        assert_eq!(tree.word_count(), 1);
+
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.insert("#Foobar");
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_bytes_long_word() {
+        let mut tree = CompletionTree::default();
+        tree.insert("a".repeat(1500));
+        tree.separator(WordSeparator::Separator("|"));
+        let mut restored = CompletionTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert_eq!(restored.words(), tree.words());
+        restored.insert("batman|robin");
+        assert!(restored.contains("robin"));
+    }
+
+    #[test]
+    fn test_capacity_after_charset_change() {
+        let mut tree = CompletionTree::default();
+        tree.set_capacity(Some(2));
+        tree.insert("port80 alpha");
+        tree.set_exclusions(&['8']);
+        tree.insert("gamma");
+        tree.insert("delta");
+        assert_eq!(tree.words(), vec!["delta".to_string(), "gamma".to_string()]);
+    }
+
+    #[test]
+    fn test_capacity_uses() {
+        let mut tree = CompletionTreeBuilder::default().capacity(Some(3)).build();
+        tree.insert("alpha bravo charlie");
+        assert_eq!(tree.complete_words("al"), Some(vec!["alpha".to_string()]));
+        assert_eq!(tree.first_completion("bra"), Some("bravo".to_string()));
+        tree.insert("delta");
+        assert_eq!(
+            tree.words(),
+            vec![
+                "alpha".to_string(),
+                "bravo".to_string(),
+                "delta".to_string()
+            ]
+        );
+        assert_eq!(tree.complete_iter("alp").count(), 1);
+        assert!(tree.complete_each("de", |_| {}));
+        tree.insert("echoes");
+        assert!(!tree.contains("bravo"));
+
+        // Words used many times are requeued once each when they reach the front
+        let mut tree = CompletionTreeBuilder::default().capacity(Some(100)).build();
+        (0..100).for_each(|i| tree.insert(format!("word{:03}", i)));
+        for _ in 0..50 {
+            tree.complete("word05");
+        }
+        (0..60).for_each(|i| tree.insert(format!("other{:03}", i)));
+        assert_eq!(tree.word_count(), 100);
+        assert_eq!(tree.complete_words("word05").map(|w| w.len()), Some(10));
+        assert!(!tree.contains("word069"));
+        assert!(tree.contains("word070"));
+    }
+
+    #[test]
+    fn test_aliases_everywhere() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batman btmagazine");
+        tree.add_alias("btm", "batmobile");
+        tree.add_alias("bt", "btmagazine");
+        let expected = vec![
+            "to the batmobile".to_string(),
+            "to the btmagazine".to_string(),
+        ];
+        assert_eq!(tree.complete("to the btm"), Some(expected.clone()));
+        assert_eq!(
+            tree.complete_words("to the btm"),
+            Some(vec!["batmobile".to_string(), "btmagazine".to_string()])
+        );
+        assert_eq!(
+            tree.complete_iter("to the btm").collect::<Vec<String>>(),
+            expected
+        );
+        let mut found = vec![];
+        assert!(tree.complete_each("to the btm", |c| found.push(c.to_string())));
+        assert_eq!(found, expected);
+        assert_eq!(
+            tree.first_completion("to the btm"),
+            Some("to the batmobile".to_string())
+        );
+        assert_eq!(
+            tree.complete_limited("to the btm", 1),
+            Some(vec!["to the batmobile".to_string()])
+        );
+        assert_eq!(tree.complete_limited("to the btm", 5), Some(expected));
+        assert_eq!(
+            tree.complete_filtered("to the btm", |word| word != "batmobile"),
+            Some(vec!["to the btmagazine".to_string()])
+        );
+        assert_eq!(
+            tree.complete_with_spans("btm"),
+            Some(vec![
+                ("batmobile".to_string(), 0..0),
+                ("btmagazine".to_string(), 0..3)
+            ])
+        );
+        assert_eq!(
+            tree.complete_at("btm x", 3),
+            Some((
+                vec!["batmobile".to_string(), "btmagazine".to_string()],
+                0..3
+            ))
+        );
+
+        // An alias matching nothing in the tree still completes
+        tree.add_alias("rb", "robin");
+        assert_eq!(tree.complete_words("rb"), Some(vec!["robin".to_string()]));
+        assert_eq!(tree.first_completion("rb"), Some("robin".to_string()));
+        assert_eq!(tree.complete_iter("rb").count(), 1);
+    }
+
+    #[test]
+    fn test_first_completion_sort_order() {
+        let mut tree = CompletionTree::default();
+        tree.insert("batmobile batman batcave");
+        for order in [
+            SortOrder::Alphabetical,
+            SortOrder::AlphabeticalReverse,
+            SortOrder::ShortestFirst,
+            SortOrder::LongestFirst,
+        ] {
+            tree.set_sort_order(order);
+            assert_eq!(
+                tree.first_completion("to the bat"),
+                tree.complete("to the bat")
+                    .and_then(|c| c.into_iter().next())
+            );
+        }
+
+        let mut tree = CompletionTree::default();
+        tree.set_case_insensitive(true);
+        tree.insert("batman Batcave");
+        assert_eq!(tree.first_completion("bat"), Some("Batcave".to_string()));
+        assert_eq!(
+            tree.first_completion("bat"),
+            tree.complete("bat").map(|c| c[0].clone())
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive_charset() {
+        let mut tree = CompletionTree::with_inclusions(&['A', 'B']);
+        tree.set_min_word_len(1);
+        tree.set_strict_charset(true);
+        tree.set_case_insensitive(true);
+        tree.insert("AB");
+        assert_eq!(tree.complete("ab"), Some(vec!["AB".to_string()]));
+
+        let mut tree = CompletionTree::with_exclusions(&['a']);
+        tree.set_min_word_len(1);
+        tree.set_case_insensitive(true);
+        tree.insert("BAT");
+        assert_eq!(tree.complete("b"), Some(vec!["BAT".to_string()]));
+    }
+
+    #[test]
+    fn test_case_insensitive_toggle() {
+        let mut tree = CompletionTree::default();
+        tree.set_track_insertion_order(true);
+        tree.build_substring_index();
+        tree.insert("batman Joker Batman batman");
+        tree.add_alias("BTM", "batmobile");
+        tree.set_case_insensitive(true);
+        assert_eq!(tree.word_count(), 2);
+        assert_eq!(tree.complete("jo"), Some(vec!["Joker".to_string()]));
+        assert_eq!(tree.complete("BAT"), Some(vec!["Batman".to_string()]));
+        assert_eq!(tree.complete("bt"), Some(vec!["batmobile".to_string()]));
+        assert_eq!(tree.search_substring("OK"), vec!["Joker".to_string()]);
+        assert_eq!(
+            tree.iter_counts().collect::<Vec<_>>(),
+            vec![("Batman".to_string(), 3), ("Joker".to_string(), 1)]
+        );
+        tree.insert("banner");
+        assert_eq!(
+            tree.complete_by_insertion_order("ba"),
+            Some(vec!["Batman".to_string(), "banner".to_string()])
+        );
+
+        tree.set_case_insensitive(false);
+        assert_eq!(
+            tree.words(),
+            vec![
+                "Batman".to_string(),
+                "Joker".to_string(),
+                "banner".to_string()
+            ]
+        );
+        assert_eq!(tree.complete("jo"), None);
+        assert_eq!(tree.complete("Jo"), Some(vec!["Joker".to_string()]));
+        assert_eq!(tree.search_substring("ok"), vec!["Joker".to_string()]);
+        assert_eq!(tree.complete("bt"), Some(vec!["batmobile".to_string()]));
+    }
 }